@@ -0,0 +1,59 @@
+use std::{fs, io, path};
+
+/// Path next to `file` that its new content is written to before it is renamed into place.
+pub fn temp_path(file: &path::Path) -> path::PathBuf {
+    let name = file
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    file.with_file_name(format!(".{}.ztr-tmp", name))
+}
+
+/// Writes `contents` to the [`temp_path`] of `file` and returns that path, ready to be
+/// renamed into place. Nothing is left behind when writing fails.
+pub fn stage(file: &path::Path, contents: impl AsRef<[u8]>) -> io::Result<path::PathBuf> {
+    let temp = temp_path(file);
+    if let Err(e) = fs::write(&temp, contents) {
+        let _ = fs::remove_file(&temp);
+        return Err(e);
+    }
+    Ok(temp)
+}
+
+/// Replaces the content of `file` by writing it next to the file first and renaming it into
+/// place, so that `file` holds either its old or its new content, even after a crash.
+pub fn write(file: &path::Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    let temp = stage(file, contents)?;
+    fs::rename(&temp, file).inspect_err(|_| {
+        let _ = fs::remove_file(&temp);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::prelude::*;
+
+    #[test]
+    fn test_write_should_replace_file_and_leave_no_temp_file() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        temp_dir.child("a.md").write_str("old").unwrap();
+        temp_dir.child(".a.md.ztr-tmp").write_str("stale").unwrap();
+
+        write(&temp_dir.path().join("a.md"), "new").unwrap();
+
+        temp_dir.child("a.md").assert("new");
+        assert!(!temp_dir.child(".a.md.ztr-tmp").exists());
+    }
+
+    #[test]
+    fn test_write_should_remove_temp_file_when_rename_fails() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        temp_dir.child("dir/keep").write_str("").unwrap();
+
+        let result = write(&temp_dir.path().join("dir"), "new");
+
+        assert!(result.is_err());
+        assert!(!temp_dir.child(".dir.ztr-tmp").exists());
+    }
+}
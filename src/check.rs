@@ -0,0 +1,269 @@
+use crate::ztr::{Error, Link, Note, Vault};
+use serde_derive::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::{fmt, path};
+
+/// A problem found by [`check`]. Paths are relative to the vault root.
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum Issue {
+    UnresolvedLink {
+        path: path::PathBuf,
+        link: Link,
+    },
+    /// No other note links to this one
+    Orphan {
+        path: path::PathBuf,
+    },
+    Untagged {
+        path: path::PathBuf,
+    },
+    DuplicateTitle {
+        title: String,
+        paths: Vec<path::PathBuf>,
+    },
+    /// Notes in different directories with the same file stem, so links cannot tell them apart
+    DuplicateId {
+        id: String,
+        paths: Vec<path::PathBuf>,
+    },
+    InvalidFrontmatter {
+        path: path::PathBuf,
+        reason: String,
+    },
+    /// The file could not be read, for instance because it is not UTF-8
+    Unreadable {
+        path: path::PathBuf,
+        reason: String,
+    },
+}
+
+impl Issue {
+    /// Orphans and untagged notes are warnings: they point at notes worth a look, while the
+    /// other issues are errors that break links, lookups or parsing.
+    pub fn is_warning(&self) -> bool {
+        matches!(self, Issue::Orphan { .. } | Issue::Untagged { .. })
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Issue::UnresolvedLink { path, link } => write!(
+                f,
+                "{}:{}:{}: unresolved link to '{}'",
+                path.display(),
+                link.line,
+                link.column,
+                link.target
+            ),
+            Issue::Orphan { path } => write!(f, "{}: no note links here", path.display()),
+            Issue::Untagged { path } => write!(f, "{}: no tags", path.display()),
+            Issue::DuplicateTitle { title, paths } => {
+                let paths: Vec<String> = paths.iter().map(|p| p.display().to_string()).collect();
+                write!(f, "{}: duplicate title '{}'", paths.join(", "), title)
+            }
+            Issue::DuplicateId { id, paths } => {
+                let paths: Vec<String> = paths.iter().map(|p| p.display().to_string()).collect();
+                write!(f, "{}: duplicate ID '{}'", paths.join(", "), id)
+            }
+            Issue::InvalidFrontmatter { path, reason } => {
+                write!(f, "{}: {}", path.display(), reason)
+            }
+            Issue::Unreadable { path, reason } => {
+                write!(f, "{}: cannot read: {}", path.display(), reason)
+            }
+        }
+    }
+}
+
+/// Every problem in `vault`: unresolved links, orphans, untagged notes, titles or IDs used by
+/// more than one note and notes whose file or frontmatter could not be read.
+pub fn check(vault: &Vault) -> Vec<Issue> {
+    let relative = |note_path: &path::Path| {
+        note_path
+            .strip_prefix(&vault.root)
+            .unwrap_or(note_path)
+            .to_path_buf()
+    };
+    let note_path = |note: &Note| relative(note.path.as_deref().unwrap_or(note.filename.as_ref()));
+
+    let mut issues = vec![];
+    for (invalid_path, e) in &vault.invalid {
+        let path = relative(invalid_path);
+        let reason = e.to_string();
+        issues.push(match e {
+            Error::Frontmatter(_) => Issue::InvalidFrontmatter { path, reason },
+            _ => Issue::Unreadable { path, reason },
+        });
+    }
+
+    let graph = vault.graph();
+    // The graph keeps the links of the last note with each ID, duplicates are reported below.
+    let owners: BTreeMap<&str, &Note> = vault
+        .notes
+        .iter()
+        .map(|note| (note.id.as_str(), note))
+        .collect();
+    let owned = |note: &Note| {
+        owners
+            .get(note.id.as_str())
+            .is_some_and(|owner| std::ptr::eq(*owner, note))
+    };
+    for note in vault.notes.iter().filter(|note| owned(note)) {
+        for edge in graph.outgoing.get(&note.id).into_iter().flatten() {
+            if edge.target.is_none() {
+                issues.push(Issue::UnresolvedLink {
+                    path: note_path(note),
+                    link: edge.link.clone(),
+                });
+            }
+        }
+    }
+
+    let linked: BTreeSet<&str> = graph
+        .edges()
+        .filter(|edge| edge.target.as_deref() != Some(edge.source.as_str()))
+        .filter_map(|edge| edge.target.as_deref())
+        .collect();
+    for note in &vault.notes {
+        if !linked.contains(note.id.as_str()) {
+            issues.push(Issue::Orphan {
+                path: note_path(note),
+            });
+        }
+    }
+
+    for note in vault.notes.iter().filter(|note| note.tags.is_empty()) {
+        issues.push(Issue::Untagged {
+            path: note_path(note),
+        });
+    }
+
+    let mut titles: BTreeMap<String, Vec<&Note>> = BTreeMap::new();
+    for note in vault.notes.iter().filter(|note| !note.title.is_empty()) {
+        titles
+            .entry(note.title.to_lowercase())
+            .or_default()
+            .push(note);
+    }
+    for notes in titles.values().filter(|notes| notes.len() > 1) {
+        issues.push(Issue::DuplicateTitle {
+            title: notes[0].title.clone(),
+            paths: notes.iter().map(|note| note_path(note)).collect(),
+        });
+    }
+
+    let mut ids: BTreeMap<&str, Vec<&Note>> = BTreeMap::new();
+    for note in &vault.notes {
+        ids.entry(note.id.as_str()).or_default().push(note);
+    }
+    for (id, notes) in ids.iter().filter(|(_, notes)| notes.len() > 1) {
+        issues.push(Issue::DuplicateId {
+            id: id.to_string(),
+            paths: notes.iter().map(|note| note_path(note)).collect(),
+        });
+    }
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::prelude::*;
+
+    fn kinds(issues: &[Issue]) -> Vec<String> {
+        issues.iter().map(|issue| issue.to_string()).collect()
+    }
+
+    #[test]
+    fn test_check_should_report_every_kind_of_issue() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        temp_dir
+            .child("a.md")
+            .write_str("---\ntags: [x]\n---\n# Alpha\n[[b]] [[gone]] [[a]]")
+            .unwrap();
+        temp_dir
+            .child("sub/b.md")
+            .write_str("---\ntags: [x]\n---\n# alpha\n[[a]]")
+            .unwrap();
+        temp_dir.child("c.md").write_str("# Gamma").unwrap();
+        temp_dir
+            .child("broken.md")
+            .write_str("---\ntags: [\n---\n")
+            .unwrap();
+        let vault = Vault::load(temp_dir.path()).unwrap();
+
+        let issues = check(&vault);
+
+        let issues = kinds(&issues);
+        assert!(issues[0].starts_with("broken.md: invalid frontmatter"));
+        assert_eq!(
+            issues[1..],
+            [
+                "a.md:5:7: unresolved link to 'gone'",
+                "c.md: no note links here",
+                "c.md: no tags",
+                "a.md, sub/b.md: duplicate title 'Alpha'",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_should_report_duplicate_ids_once() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        temp_dir
+            .child("a.md")
+            .write_str("---\ntags: [x]\n---\n# One\n[[a]]")
+            .unwrap();
+        temp_dir
+            .child("sub/a.md")
+            .write_str("---\ntags: [x]\n---\n# Two\n[[gone]]")
+            .unwrap();
+        let vault = Vault::load(temp_dir.path()).unwrap();
+
+        let issues = check(&vault);
+
+        assert_eq!(
+            kinds(&issues),
+            [
+                "sub/a.md:5:1: unresolved link to 'gone'",
+                "a.md: no note links here",
+                "sub/a.md: no note links here",
+                "a.md, sub/a.md: duplicate ID 'a'",
+            ]
+        );
+        assert!(!issues[3].is_warning());
+    }
+
+    #[test]
+    fn test_is_warning_should_hold_for_orphans_and_untagged_notes() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        temp_dir
+            .child("a.md")
+            .write_str("# Alpha\n[[gone]]")
+            .unwrap();
+        let vault = Vault::load(temp_dir.path()).unwrap();
+
+        let issues = check(&vault);
+
+        let warnings: Vec<bool> = issues.iter().map(Issue::is_warning).collect();
+        assert_eq!(warnings, vec![false, true, true]);
+    }
+
+    #[test]
+    fn test_check_should_pass_linked_tagged_notes() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        temp_dir
+            .child("a.md")
+            .write_str("---\ntags: [x]\n---\n[b](b.md)")
+            .unwrap();
+        temp_dir
+            .child("b.md")
+            .write_str("---\ntags: [x]\n---\n[[a]]")
+            .unwrap();
+        let vault = Vault::load(temp_dir.path()).unwrap();
+
+        assert!(check(&vault).is_empty());
+    }
+}
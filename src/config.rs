@@ -0,0 +1,293 @@
+use crate::kinds::BUILTIN_KINDS;
+use crate::ztr::{DefaultNote, Error, JournalConfig, NoteIdScheme, NoteKind, Result};
+use serde_derive::Deserialize;
+use std::collections::BTreeMap;
+use std::{env, fs, io, path};
+
+/// Settings merged from the global `ztr.toml` and the per-vault `.ztr/config.toml`.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct Config {
+    pub root: Option<path::PathBuf>,
+    pub id_scheme: NoteIdScheme,
+    pub editor: Option<String>,
+    pub note: DefaultNote,
+    /// Named queries, run with `ztr saved <name>`
+    pub saved: BTreeMap<String, String>,
+    /// Kinds of notes beyond `fleeting`, `literature` and `permanent`, or settings for those
+    pub kinds: BTreeMap<String, NoteKind>,
+    /// Fleeting notes older than this, such as `7d`, are listed by `ztr inbox`
+    pub inbox_age: Option<String>,
+    /// Where daily, weekly and monthly entries go and how they are rendered
+    pub journal: JournalConfig,
+}
+
+impl Config {
+    pub fn saved_query(&self, name: &str) -> Result<&str> {
+        self.saved.get(name).map(String::as_str).ok_or_else(|| {
+            Error::NotFound(format!(
+                "no saved search '{}' in the [saved] config table",
+                name
+            ))
+        })
+    }
+
+    /// Settings of the kind `name`, the defaults for a built-in kind missing from `[kinds]`.
+    pub fn kind(&self, name: &str) -> Result<NoteKind> {
+        match self.kinds.get(name) {
+            Some(kind) => Ok(kind.clone()),
+            None if BUILTIN_KINDS.contains(&name) => Ok(NoteKind::default()),
+            None => Err(Error::NotFound(format!(
+                "no kind '{}', expected one of {} or a [kinds] config table",
+                name,
+                BUILTIN_KINDS.join(", ")
+            ))),
+        }
+    }
+
+    pub fn is_kind(&self, name: &str) -> bool {
+        BUILTIN_KINDS.contains(&name) || self.kinds.contains_key(name)
+    }
+}
+
+pub fn global_config_path() -> Option<path::PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(path::PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| path::PathBuf::from(home).join(".config")))
+        .map(|dir| dir.join("ztr").join("ztr.toml"))
+}
+
+pub fn vault_config_path(zk_root: &path::Path) -> path::PathBuf {
+    zk_root.join(".ztr").join("config.toml")
+}
+
+/// Loads every layer in order, later layers overriding the keys of earlier ones.
+/// Missing files are skipped.
+pub fn load(layers: &[path::PathBuf]) -> Result<Config> {
+    let mut merged = toml::Table::new();
+    for layer in layers {
+        merge(&mut merged, load_table(layer)?);
+    }
+
+    merged
+        .try_into()
+        .map_err(|e: toml::de::Error| Error::Config(e.message().to_string()))
+}
+
+fn load_table(config_path: &path::Path) -> Result<toml::Table> {
+    match fs::read_to_string(config_path) {
+        Ok(raw) => raw
+            .parse()
+            .map_err(|e| Error::Config(format!("{}: {}", config_path.display(), e))),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(toml::Table::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn merge(base: &mut toml::Table, overlay: toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base_table)), toml::Value::Table(overlay_table)) => {
+                merge(base_table, overlay_table)
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Picks the zettelkasten root with the precedence `--root`, then `ZTR_ROOT`,
+/// then the config file. The first one that is set wins and must be an existing directory.
+pub fn resolve_root(
+    flag: Option<path::PathBuf>,
+    env: Option<path::PathBuf>,
+    config: Option<path::PathBuf>,
+) -> Result<path::PathBuf> {
+    let (source, root) = flag
+        .map(|root| ("--root", root))
+        .or(env.map(|root| ("ZTR_ROOT", root)))
+        .or(config.map(|root| ("config file", root)))
+        .ok_or_else(|| {
+            Error::NotFound(String::from(
+                "no zettelkasten root configured, use --root, ZTR_ROOT or set root in ztr.toml",
+            ))
+        })?;
+
+    if !root.is_dir() {
+        return Err(Error::NotFound(format!(
+            "zettelkasten root '{}' from {} is not an existing directory",
+            root.display(),
+            source
+        )));
+    }
+
+    Ok(root)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::prelude::*;
+
+    #[test]
+    fn test_resolve_root_should_prefer_flag() {
+        let flag_dir = assert_fs::TempDir::new().unwrap();
+        let env_dir = assert_fs::TempDir::new().unwrap();
+
+        let result = resolve_root(
+            Some(flag_dir.path().to_path_buf()),
+            Some(env_dir.path().to_path_buf()),
+            None,
+        );
+
+        assert_eq!(result.unwrap(), flag_dir.path());
+    }
+
+    #[test]
+    fn test_resolve_root_should_prefer_env_over_config() {
+        let env_dir = assert_fs::TempDir::new().unwrap();
+        let config_dir = assert_fs::TempDir::new().unwrap();
+
+        let result = resolve_root(
+            None,
+            Some(env_dir.path().to_path_buf()),
+            Some(config_dir.path().to_path_buf()),
+        );
+
+        assert_eq!(result.unwrap(), env_dir.path());
+    }
+
+    #[test]
+    fn test_resolve_root_should_fail_without_any_source() {
+        let result = resolve_root(None, None, None);
+
+        assert!(matches!(result.unwrap_err(), Error::NotFound(_)));
+    }
+
+    #[test]
+    fn test_resolve_root_should_fail_on_missing_directory() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+
+        let result = resolve_root(Some(temp_dir.path().join("missing")), None, None);
+
+        assert!(matches!(result.unwrap_err(), Error::NotFound(_)));
+    }
+
+    #[test]
+    fn test_load_should_read_root() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let config_file = temp_dir.child("ztr.toml");
+        config_file.write_str("root = \"/tmp/notes\"\n").unwrap();
+
+        let config = load(&[config_file.to_path_buf()]).unwrap();
+
+        assert_eq!(config.root, Some(path::PathBuf::from("/tmp/notes")));
+    }
+
+    #[test]
+    fn test_load_should_default_missing_file() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+
+        let config = load(&[temp_dir.path().join("ztr.toml")]).unwrap();
+
+        assert_eq!(config.root, None);
+        assert_eq!(config.note.tags, vec![String::from("fleeting")]);
+    }
+
+    #[test]
+    fn test_load_should_keep_defaults_for_missing_note_keys() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let config_file = temp_dir.child("ztr.toml");
+        config_file
+            .write_str("[note]\ntitle = \"Untitled\"\n")
+            .unwrap();
+
+        let config = load(&[config_file.to_path_buf()]).unwrap();
+
+        assert_eq!(config.note.title, "Untitled");
+        assert_eq!(config.note.content, DefaultNote::new().content);
+    }
+
+    #[test]
+    fn test_load_should_let_vault_override_global() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let global_file = temp_dir.child("ztr.toml");
+        global_file
+            .write_str("editor = \"vim\"\n[note]\ntitle = \"Global\"\ntags = [\"inbox\"]\n")
+            .unwrap();
+        let vault_file = temp_dir.child(".ztr/config.toml");
+        vault_file.write_str("[note]\ntitle = \"Vault\"\n").unwrap();
+
+        let config = load(&[global_file.to_path_buf(), vault_file.to_path_buf()]).unwrap();
+
+        assert_eq!(config.editor, Some(String::from("vim")));
+        assert_eq!(config.note.title, "Vault");
+        assert_eq!(config.note.tags, vec![String::from("inbox")]);
+    }
+
+    #[test]
+    fn test_load_should_merge_saved_searches() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let global_file = temp_dir.child("ztr.toml");
+        global_file
+            .write_str("[saved]\ninbox = \"tag:fleeting\"\nrecent = \"age<7d\"\n")
+            .unwrap();
+        let vault_file = temp_dir.child(".ztr/config.toml");
+        vault_file
+            .write_str("[saved]\ninbox = \"tag:fleeting AND age>7d\"\n")
+            .unwrap();
+
+        let config = load(&[global_file.to_path_buf(), vault_file.to_path_buf()]).unwrap();
+
+        assert_eq!(
+            config.saved_query("inbox").unwrap(),
+            "tag:fleeting AND age>7d"
+        );
+        assert_eq!(config.saved_query("recent").unwrap(), "age<7d");
+        assert!(matches!(
+            config.saved_query("nope"),
+            Err(Error::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_load_should_read_kinds() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let config_file = temp_dir.child("ztr.toml");
+        config_file
+            .write_str(
+                "[kinds.fleeting]
+dir = \"inbox\"
+[kinds.project]
+template = \"project\"
+",
+            )
+            .unwrap();
+
+        let config = load(&[config_file.to_path_buf()]).unwrap();
+
+        assert_eq!(
+            config.kind("fleeting").unwrap().dir,
+            Some(path::PathBuf::from("inbox"))
+        );
+        assert_eq!(
+            config.kind("project").unwrap().template.as_deref(),
+            Some("project")
+        );
+        assert_eq!(config.kind("permanent").unwrap(), NoteKind::default());
+        assert!(matches!(config.kind("nope"), Err(Error::NotFound(_))));
+    }
+
+    #[test]
+    fn test_load_should_fail_on_invalid_toml() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let config_file = temp_dir.child("ztr.toml");
+        config_file.write_str("root = \n").unwrap();
+
+        let result = load(&[config_file.to_path_buf()]);
+
+        assert!(matches!(result.err().unwrap(), Error::Config(_)));
+    }
+}
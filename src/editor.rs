@@ -0,0 +1,145 @@
+use crate::ztr::{Error, Note, Result};
+use std::{env, path, process};
+
+/// The editor command from the config, `$VISUAL` or `$EDITOR`, falling back to `vi`.
+pub fn editor_command(configured: Option<&str>) -> String {
+    configured
+        .map(String::from)
+        .or_else(|| env::var("VISUAL").ok())
+        .or_else(|| env::var("EDITOR").ok())
+        .filter(|command| !command.trim().is_empty())
+        .unwrap_or_else(|| String::from("vi"))
+}
+
+/// Splits `command` into words, replacing `{path}` and `{line}`. The path is appended
+/// when the command has no `{path}` placeholder.
+pub fn editor_args(command: &str, note_path: &path::Path, line: usize) -> Vec<String> {
+    let note_path = note_path.to_string_lossy();
+    let mut args: Vec<String> = split_words(command)
+        .into_iter()
+        .map(|word| {
+            word.replace("{path}", &note_path)
+                .replace("{line}", &line.to_string())
+        })
+        .collect();
+    if !command.contains("{path}") {
+        args.push(note_path.to_string());
+    }
+    args
+}
+
+/// Runs the editor on `note_path` and waits for it to exit, then parses the note
+/// again so a broken frontmatter is reported right away.
+pub fn edit(command: &str, note_path: &path::Path, line: usize) -> Result<Note> {
+    let args = editor_args(command, note_path, line);
+    let Some((program, args)) = args.split_first() else {
+        return Err(Error::Editor(String::from("empty editor command")));
+    };
+
+    let status = process::Command::new(program)
+        .args(args)
+        .status()
+        .map_err(|e| Error::Editor(format!("could not start '{}': {}", program, e)))?;
+    if !status.success() {
+        return Err(Error::Editor(format!(
+            "'{}' exited with {}",
+            program, status
+        )));
+    }
+
+    Note::from_path(note_path)
+}
+
+/// Whitespace separated words, where single or double quotes keep whitespace together.
+fn split_words(command: &str) -> Vec<String> {
+    let mut words = vec![];
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote = None;
+    for c in command.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => word.push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            (None, c) => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::prelude::*;
+
+    #[test]
+    fn test_editor_args_should_append_path() {
+        let args = editor_args("code --wait", path::Path::new("/notes/a b.md"), 1);
+
+        assert_eq!(args, vec!["code", "--wait", "/notes/a b.md"]);
+    }
+
+    #[test]
+    fn test_editor_args_should_fill_placeholders() {
+        let args = editor_args(
+            "nvim '+{line}' \"{path}\"",
+            path::Path::new("/notes/a.md"),
+            7,
+        );
+
+        assert_eq!(args, vec!["nvim", "+7", "/notes/a.md"]);
+    }
+
+    #[test]
+    fn test_editor_command_should_prefer_config() {
+        assert_eq!(editor_command(Some("hx")), "hx");
+    }
+
+    #[test]
+    fn test_edit_should_return_revalidated_note() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let note_file = temp_dir.child("a.md");
+        note_file.write_str("# Before").unwrap();
+
+        let note = edit("sed -i s/Before/After/ {path}", note_file.path(), 1).unwrap();
+
+        assert_eq!(note.title, "After");
+    }
+
+    #[test]
+    fn test_edit_should_report_broken_frontmatter() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let note_file = temp_dir.child("a.md");
+        note_file.write_str("# Title").unwrap();
+
+        let result = edit("sed -i 1i--- {path}", note_file.path(), 1);
+
+        assert!(matches!(result, Err(Error::Frontmatter(_))));
+    }
+
+    #[test]
+    fn test_edit_should_fail_when_editor_fails() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let note_file = temp_dir.child("a.md");
+        note_file.write_str("# Title").unwrap();
+
+        let result = edit("false", note_file.path(), 1);
+
+        assert!(matches!(result, Err(Error::Editor(_))));
+    }
+}
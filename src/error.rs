@@ -0,0 +1,74 @@
+use std::{io, path};
+use thiserror::Error;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    #[error("{}: {source}", .path.display())]
+    File {
+        path: path::PathBuf,
+        source: io::Error,
+    },
+
+    #[error("invalid template: {0}")]
+    TemplateParse(Box<handlebars::TemplateError>),
+
+    #[error("could not render template: {0}")]
+    TemplateRender(Box<handlebars::RenderError>),
+
+    #[error("invalid config: {0}")]
+    Config(String),
+
+    #[error("{0}")]
+    NotFound(String),
+
+    #[error("template variables not supplied: {}", .0.join(", "))]
+    MissingVariables(Vec<String>),
+
+    #[error("invalid frontmatter: {0}")]
+    Frontmatter(String),
+
+    #[error("'{query}' matches several notes: {}", .candidates.join(", "))]
+    Ambiguous {
+        query: String,
+        candidates: Vec<String>,
+    },
+
+    #[error("editor failed: {0}")]
+    Editor(String),
+
+    #[error("invalid note id: {0}")]
+    InvalidId(String),
+
+    #[error("no free note name in '{}' after {attempts} attempts, last tried '{name}'", .root.display())]
+    Collision {
+        root: path::PathBuf,
+        name: String,
+        attempts: usize,
+    },
+
+    #[error("'{id}' is linked from {}, use --force to delete it anyway", .sources.join(", "))]
+    HasBacklinks { id: String, sources: Vec<String> },
+
+    #[error("invalid query: {0}")]
+    InvalidQuery(String),
+
+    #[error("invalid tag: {0}")]
+    InvalidTag(String),
+}
+
+impl From<handlebars::TemplateError> for Error {
+    fn from(e: handlebars::TemplateError) -> Self {
+        Error::TemplateParse(Box::new(e))
+    }
+}
+
+impl From<handlebars::RenderError> for Error {
+    fn from(e: handlebars::RenderError) -> Self {
+        Error::TemplateRender(Box::new(e))
+    }
+}
@@ -0,0 +1,183 @@
+use crate::links::Link;
+use crate::ztr::Note;
+use serde_derive::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+/// A link of the note `source`, with the ID of the note it resolved to.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Edge {
+    pub source: String,
+    /// `None` when no note matches the link target
+    pub target: Option<String>,
+    pub link: Link,
+}
+
+/// Outgoing links of every note in a vault.
+#[derive(Default, Debug)]
+pub struct Graph {
+    /// Edges keyed by the ID of the linking note, in order of appearance
+    pub outgoing: BTreeMap<String, Vec<Edge>>,
+}
+
+impl Graph {
+    /// Resolves link targets by filename, then ID, then title and then the `aliases`
+    /// in the frontmatter, ignoring case for titles and aliases.
+    pub fn build(notes: &[Note]) -> Graph {
+        let resolver = Resolver::new(notes);
+        let mut graph = Graph::default();
+        for note in notes {
+            let edges = note
+                .links
+                .iter()
+                .map(|link| Edge {
+                    source: note.id.clone(),
+                    target: resolver.resolve(&link.target).map(String::from),
+                    link: link.clone(),
+                })
+                .collect();
+            graph.outgoing.insert(note.id.clone(), edges);
+        }
+        graph
+    }
+
+    pub fn edges(&self) -> impl Iterator<Item = &Edge> {
+        self.outgoing.values().flatten()
+    }
+
+    /// Links whose target matches no note.
+    pub fn unresolved(&self) -> impl Iterator<Item = &Edge> {
+        self.edges().filter(|edge| edge.target.is_none())
+    }
+
+    /// Links pointing at the note `id`, from other notes as well as itself.
+    pub fn backlinks<'a>(&'a self, id: &'a str) -> impl Iterator<Item = &'a Edge> {
+        self.edges()
+            .filter(move |edge| edge.target.as_deref() == Some(id))
+    }
+}
+
+struct Resolver<'a> {
+    by_filename: HashMap<&'a str, &'a str>,
+    by_id: HashMap<&'a str, &'a str>,
+    by_name: HashMap<String, &'a str>,
+}
+
+impl<'a> Resolver<'a> {
+    fn new(notes: &'a [Note]) -> Self {
+        let mut resolver = Resolver {
+            by_filename: HashMap::new(),
+            by_id: HashMap::new(),
+            by_name: HashMap::new(),
+        };
+        for note in notes {
+            resolver
+                .by_filename
+                .entry(&note.filename)
+                .or_insert(&note.id);
+            resolver.by_id.entry(&note.id).or_insert(&note.id);
+        }
+        // Titles take precedence over aliases of other notes.
+        for note in notes {
+            if !note.title.is_empty() {
+                resolver
+                    .by_name
+                    .entry(note.title.to_lowercase())
+                    .or_insert(&note.id);
+            }
+        }
+        for note in notes {
+            for alias in aliases(note) {
+                resolver
+                    .by_name
+                    .entry(alias.to_lowercase())
+                    .or_insert(&note.id);
+            }
+        }
+        resolver
+    }
+
+    fn resolve(&self, target: &str) -> Option<&'a str> {
+        let filename = target.rsplit('/').next().unwrap_or(target);
+        self.by_filename
+            .get(filename)
+            .or_else(|| self.by_id.get(filename))
+            .or_else(|| self.by_name.get(&target.to_lowercase()))
+            .copied()
+    }
+}
+
+/// The frontmatter `aliases`, as a list or a single string.
+pub fn aliases(note: &Note) -> Vec<&str> {
+    match note.vars.get("aliases") {
+        Some(serde_json::Value::Array(aliases)) => {
+            aliases.iter().filter_map(|alias| alias.as_str()).collect()
+        }
+        Some(serde_json::Value::String(alias)) => vec![alias.as_str()],
+        _ => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn notes() -> Vec<Note> {
+        [
+            (
+                "a.md",
+                "---\naliases: [First]\n---\n# Alpha\n[[b]] [[Gamma]] [x](sub/c.md)",
+            ),
+            ("b.md", "# Beta\n[[first|back to a]] [[missing]]"),
+            ("c.md", "# Gamma\n[[a.md]]"),
+        ]
+        .iter()
+        .map(|(filename, raw)| Note::parse(filename, raw).unwrap())
+        .collect()
+    }
+
+    fn targets<'a>(edges: impl Iterator<Item = &'a Edge>) -> Vec<Option<&'a str>> {
+        edges.map(|edge| edge.target.as_deref()).collect()
+    }
+
+    #[test]
+    fn test_build_should_resolve_by_id_title_filename_and_alias() {
+        let graph = Graph::build(&notes());
+
+        assert_eq!(
+            targets(graph.outgoing["a"].iter()),
+            vec![Some("b"), Some("c"), Some("c")]
+        );
+        assert_eq!(targets(graph.outgoing["b"].iter()), vec![Some("a"), None]);
+        assert_eq!(targets(graph.outgoing["c"].iter()), vec![Some("a")]);
+    }
+
+    #[test]
+    fn test_build_should_count_lines_from_file_start() {
+        let graph = Graph::build(&notes());
+
+        assert_eq!(graph.outgoing["a"][0].link.line, 5);
+        assert_eq!(graph.outgoing["b"][0].link.line, 2);
+    }
+
+    #[test]
+    fn test_unresolved_should_list_missing_targets() {
+        let graph = Graph::build(&notes());
+
+        let unresolved: Vec<&str> = graph
+            .unresolved()
+            .map(|edge| edge.link.target.as_str())
+            .collect();
+        assert_eq!(unresolved, vec!["missing"]);
+    }
+
+    #[test]
+    fn test_backlinks_should_list_linking_notes() {
+        let graph = Graph::build(&notes());
+
+        let sources: Vec<&str> = graph
+            .backlinks("a")
+            .map(|edge| edge.source.as_str())
+            .collect();
+        assert_eq!(sources, vec!["b", "c"]);
+    }
+}
@@ -0,0 +1,211 @@
+use crate::id;
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, FixedOffset, Local, Offset, Utc};
+use handlebars::{
+    handlebars_helper, Context, Handlebars, Helper, HelperResult, Output, RenderContext,
+    RenderError,
+};
+use serde_json::Value;
+use std::env;
+
+/// Helpers that come with handlebars itself.
+const HANDLEBARS_HELPERS: &[&str] = &[
+    "if", "unless", "each", "with", "lookup", "raw", "log", "eq", "ne", "gt", "gte", "lt", "lte",
+    "and", "or", "not", "len",
+];
+
+/// Helpers added by [`register`].
+const ZTR_HELPERS: &[&str] = &[
+    "now", "date", "slug", "id", "uuid", "upper", "lower", "join", "env",
+];
+
+pub fn is_helper(name: &str) -> bool {
+    HANDLEBARS_HELPERS.contains(&name) || ZTR_HELPERS.contains(&name)
+}
+
+/// Registers the built-in helpers available to note templates and ID patterns:
+///
+/// - `now` (alias `date`): current time, `{{now "%Y-%m-%d" tz="UTC"}}`, RFC 3339 by default.
+///   `tz` is `UTC` (or `Z`), `local` or a fixed offset such as `+02:00`; zone names such as
+///   `Europe/Paris` are not supported
+/// - `slug`: link-safe slug of a string
+/// - `id`: ID of the note, or a fresh one with `{{id "random"}}`, `"ulid"` or `"timestamp"`
+/// - `uuid`: random UUID v4
+/// - `upper`, `lower`: change the case of a string
+/// - `join`: join a list, `{{join tags ", "}}`
+/// - `env`: environment variable with an optional default, `{{env "USER" "anonymous"}}`
+pub fn register(hb: &mut Handlebars) {
+    hb.register_helper("now", Box::new(now_helper));
+    hb.register_helper("date", Box::new(now_helper));
+    hb.register_helper("slug", Box::new(slug_helper));
+    hb.register_helper("id", Box::new(id_helper));
+    hb.register_helper("uuid", Box::new(uuid_helper));
+    hb.register_helper("upper", Box::new(upper_helper));
+    hb.register_helper("lower", Box::new(lower_helper));
+    hb.register_helper("join", Box::new(join_helper));
+    hb.register_helper("env", Box::new(env_helper));
+}
+
+handlebars_helper!(slug_helper: |text: str| id::slugify(text));
+handlebars_helper!(uuid_helper: | | uuid::Uuid::new_v4().to_string());
+handlebars_helper!(upper_helper: |text: str| text.to_uppercase());
+handlebars_helper!(lower_helper: |text: str| text.to_lowercase());
+// `args` holds every param including the named ones, so optional params start at 1.
+handlebars_helper!(join_helper: |list: array, *args| {
+    let separator = args.get(1).and_then(|s| s.as_str()).unwrap_or(", ");
+    list.iter()
+        .map(|item| match item {
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(separator)
+});
+handlebars_helper!(env_helper: |name: str, *args| {
+    env::var(name).unwrap_or_else(|_| {
+        args.get(1).and_then(|d| d.as_str()).unwrap_or("").to_string()
+    })
+});
+
+fn now_helper(
+    h: &Helper,
+    _: &Handlebars,
+    _: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let now = match h.hash_get("tz").map(|tz| tz.value()) {
+        None => Local::now().fixed_offset(),
+        Some(Value::String(tz)) => now_in(tz).ok_or_else(|| {
+            RenderError::new(format!(
+                "now: unknown timezone '{}', use UTC, local or an offset such as +02:00",
+                tz
+            ))
+        })?,
+        Some(_) => return Err(RenderError::new("now: tz must be a string")),
+    };
+
+    match h.param(0).map(|format| format.value()) {
+        None => out.write(&now.to_rfc3339())?,
+        Some(Value::String(format)) => {
+            if StrftimeItems::new(format).any(|item| matches!(item, Item::Error)) {
+                return Err(RenderError::new(format!(
+                    "now: invalid date format '{}'",
+                    format
+                )));
+            }
+            out.write(&now.format(format).to_string())?
+        }
+        Some(_) => return Err(RenderError::new("now: format must be a string")),
+    }
+    Ok(())
+}
+
+/// Current time in `UTC`, `local` or a fixed offset such as `+02:00`.
+fn now_in(tz: &str) -> Option<DateTime<FixedOffset>> {
+    let offset = match tz {
+        "UTC" | "utc" | "Z" => Utc.fix(),
+        "local" => return Some(Local::now().fixed_offset()),
+        offset => offset.parse::<FixedOffset>().ok()?,
+    };
+    Some(Utc::now().with_timezone(&offset))
+}
+
+fn id_helper(
+    h: &Helper,
+    _: &Handlebars,
+    ctx: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let generated = match h.param(0).map(|scheme| scheme.value()) {
+        None => match ctx.data().get("id") {
+            Some(Value::String(note_id)) => note_id.clone(),
+            _ => String::new(),
+        },
+        Some(Value::String(scheme)) => match scheme.as_str() {
+            "random" => id::random(),
+            "ulid" => ulid::Ulid::new().to_string().to_lowercase(),
+            "timestamp" => Local::now().format("%Y%m%d%H%M%S").to_string(),
+            other => {
+                return Err(RenderError::new(format!(
+                    "id: unknown scheme '{}', use random, ulid or timestamp",
+                    other
+                )))
+            }
+        },
+        Some(_) => return Err(RenderError::new("id: scheme must be a string")),
+    };
+    out.write(&generated)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn render(template: &str, data: &Value) -> Result<String, RenderError> {
+        let mut hb = Handlebars::new();
+        register(&mut hb);
+        hb.render_template(template, data)
+    }
+
+    #[test]
+    fn test_now_should_format_in_timezone() {
+        let output = render("{{now \"%z\" tz=\"+02:00\"}}", &json!({})).unwrap();
+
+        assert_eq!(output, "+0200");
+    }
+
+    #[test]
+    fn test_now_should_reject_unknown_timezone() {
+        let error = render("{{now tz=\"Mars/Olympus\"}}", &json!({})).unwrap_err();
+
+        assert!(error
+            .to_string()
+            .contains("use UTC, local or an offset such as +02:00"));
+    }
+
+    #[test]
+    fn test_id_should_default_to_note_id() {
+        let output = render(
+            "{{#each tags}}{{id}}{{/each}}",
+            &json!({"id": "abc", "tags": ["x"]}),
+        )
+        .unwrap();
+
+        assert_eq!(output, "abc");
+    }
+
+    #[test]
+    fn test_string_helpers_should_transform_values() {
+        let data = json!({"title": "Hello World", "tags": ["a", "b"]});
+
+        let output = render(
+            "{{slug title}}|{{upper title}}|{{lower title}}|{{join tags \" \"}}|{{join tags}}",
+            &data,
+        )
+        .unwrap();
+
+        assert_eq!(output, "hello-world|HELLO WORLD|hello world|a b|a, b");
+    }
+
+    #[test]
+    fn test_env_should_fall_back_to_default() {
+        let output = render(
+            "{{env \"ZTR_TEST_UNSET_VARIABLE\" \"fallback\"}}",
+            &json!({}),
+        )
+        .unwrap();
+
+        assert_eq!(output, "fallback");
+    }
+
+    #[test]
+    fn test_uuid_should_render_v4() {
+        let output = render("{{uuid}}", &json!({})).unwrap();
+
+        assert_eq!(uuid::Uuid::parse_str(&output).unwrap().get_version_num(), 4);
+    }
+}
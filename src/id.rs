@@ -0,0 +1,329 @@
+use crate::vault::note_paths;
+use crate::ztr::{Error, Result};
+use handlebars::Handlebars;
+use rand::Rng;
+use serde_derive::Deserialize;
+use std::cell::Cell;
+use std::{iter, path};
+
+/// How the filename of a new note is chosen.
+///
+/// Selected in config with `id_scheme = "timestamp"`, or
+/// `id_scheme = { pattern = "{{date \"%Y%m%d\"}}-{{slug title}}" }` for a custom pattern.
+#[derive(Deserialize, Default, Clone, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum NoteIdScheme {
+    /// 10 random base36 characters
+    #[default]
+    Random,
+    /// Local time as `YYYYMMDDHHmmss`, like The Archive
+    Timestamp,
+    /// Lowercase ULID
+    Ulid,
+    /// Luhmann-style `1a2b`, branching off the parent note when one is given
+    Folgezettel,
+    /// The title as a link-safe slug
+    Slug,
+    /// Handlebars pattern with `title` and the built-in helpers such as `date` and `slug`
+    Pattern(String),
+}
+
+impl NoteIdScheme {
+    /// Builds the generator for a note titled `title` in `zk_root`.
+    ///
+    /// Every call yields the next candidate for when the previous one was taken. `Random` and
+    /// `Ulid` produce a fresh ID, Folgezettel moves on to the next sibling and the other schemes
+    /// append `-2`, `-3`, ... to the ID.
+    pub fn generator(
+        &self,
+        zk_root: &path::Path,
+        title: &str,
+        parent: Option<&str>,
+    ) -> Result<Box<dyn Fn() -> String>> {
+        let id = match self {
+            NoteIdScheme::Random => return Ok(Box::new(random)),
+            NoteIdScheme::Ulid => {
+                return Ok(Box::new(|| ulid::Ulid::new().to_string().to_lowercase()))
+            }
+            NoteIdScheme::Timestamp => timestamp("%Y%m%d%H%M%S"),
+            NoteIdScheme::Folgezettel => next_folgezettel(&note_ids(zk_root)?, parent)?,
+            NoteIdScheme::Slug => match slugify(title) {
+                slug if slug.is_empty() => random(),
+                slug => slug,
+            },
+            NoteIdScheme::Pattern(pattern) => render_pattern(pattern, title)?,
+        };
+
+        let attempt = Cell::new(0);
+        let folgezettel = *self == NoteIdScheme::Folgezettel;
+        Ok(Box::new(move || {
+            let n = attempt.replace(attempt.get() + 1);
+            match n {
+                0 => id.clone(),
+                _ if folgezettel => bump_folgezettel(&id, n),
+                _ => format!("{}-{}", id, n + 1),
+            }
+        }))
+    }
+}
+
+pub fn random() -> String {
+    let len = 10;
+    const CHARSET: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+    let mut rng = rand::thread_rng();
+    let one_char = || CHARSET[rng.gen_range(0..CHARSET.len())] as char;
+    iter::repeat_with(one_char).take(len).collect()
+}
+
+/// Lowercases `text` and joins its alphanumeric runs with `-`.
+pub fn slugify(text: &str) -> String {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+fn timestamp(format: &str) -> String {
+    chrono::Local::now().format(format).to_string()
+}
+
+fn render_pattern(pattern: &str, title: &str) -> Result<String> {
+    let mut hb = Handlebars::new();
+    crate::helpers::register(&mut hb);
+
+    hb.register_template_string("id", pattern)?;
+    let id = hb.render("id", &serde_json::json!({ "title": title }))?;
+
+    if id.is_empty() || id.contains(path::is_separator) {
+        return Err(Error::InvalidId(format!(
+            "pattern '{}' rendered '{}'",
+            pattern, id
+        )));
+    }
+
+    Ok(id)
+}
+
+/// IDs of every note in the vault, in subdirectories too, since IDs are unique vault-wide.
+pub fn note_ids(zk_root: &path::Path) -> Result<Vec<String>> {
+    Ok(note_paths(zk_root)?
+        .iter()
+        .filter_map(|note_path| note_path.file_stem())
+        .map(|stem| stem.to_string_lossy().to_string())
+        .collect())
+}
+
+/// Splits a Folgezettel ID into its alternating number and letter segments,
+/// or `None` if it is not one.
+fn folgezettel_segments(id: &str) -> Option<Vec<&str>> {
+    let mut segments = vec![];
+    let mut start = 0;
+    for (i, c) in id.char_indices().skip(1) {
+        let prev = id[..i].chars().next_back()?;
+        if prev.is_ascii_digit() != c.is_ascii_digit() {
+            segments.push(&id[start..i]);
+            start = i;
+        }
+    }
+    segments.push(&id[start..]);
+
+    let well_formed = segments.iter().enumerate().all(|(i, segment)| {
+        if i % 2 == 0 {
+            segment.chars().all(|c| c.is_ascii_digit()) && !segment.starts_with('0')
+        } else {
+            segment.chars().all(|c| c.is_ascii_lowercase())
+        }
+    });
+    (!id.is_empty() && well_formed).then_some(segments)
+}
+
+fn letters_to_number(letters: &str) -> u64 {
+    letters
+        .bytes()
+        .fold(0, |n, b| n * 26 + u64::from(b - b'a' + 1))
+}
+
+fn number_to_letters(mut n: u64) -> String {
+    let mut letters = vec![];
+    while n > 0 {
+        n -= 1;
+        letters.push((b'a' + (n % 26) as u8) as char);
+        n /= 26;
+    }
+    letters.iter().rev().collect()
+}
+
+/// Moves the last segment of a Folgezettel ID `by` siblings along.
+fn bump_folgezettel(id: &str, by: u64) -> String {
+    let segments = folgezettel_segments(id).unwrap_or_default();
+    let Some((last, init)) = segments.split_last() else {
+        return format!("{}-{}", id, by + 1);
+    };
+
+    let bumped = if segments.len().is_multiple_of(2) {
+        number_to_letters(letters_to_number(last) + by)
+    } else {
+        (last.parse::<u64>().unwrap_or(0) + by).to_string()
+    };
+    format!("{}{}", init.concat(), bumped)
+}
+
+/// Picks the next free Folgezettel ID, either the next top level number or
+/// the next branch below `parent`.
+fn next_folgezettel(existing: &[String], parent: Option<&str>) -> Result<String> {
+    let prefix = parent.unwrap_or("");
+    let parent_depth = match parent {
+        Some(parent) => folgezettel_segments(parent)
+            .ok_or_else(|| Error::InvalidId(format!("'{}' is not a Folgezettel id", parent)))?
+            .len(),
+        None => 0,
+    };
+    let letters = parent_depth % 2 == 1;
+
+    let last = existing
+        .iter()
+        .filter_map(|id| folgezettel_segments(id))
+        .filter(|segments| segments.len() > parent_depth)
+        .filter(|segments| segments[..parent_depth].concat() == prefix)
+        .map(|segments| {
+            if letters {
+                letters_to_number(segments[parent_depth])
+            } else {
+                segments[parent_depth].parse().unwrap_or(u64::MAX - 1)
+            }
+        })
+        .max()
+        .unwrap_or(0);
+
+    let next = last + 1;
+    Ok(if letters {
+        format!("{}{}", prefix, number_to_letters(next))
+    } else {
+        format!("{}{}", prefix, next)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::prelude::*;
+
+    fn ids(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    #[test]
+    fn test_slugify_should_join_words_with_dashes() {
+        assert_eq!(slugify("  Hello, World! Zettel 2 "), "hello-world-zettel-2");
+    }
+
+    #[test]
+    fn test_folgezettel_should_start_at_one() {
+        assert_eq!(next_folgezettel(&[], None).unwrap(), "1");
+    }
+
+    #[test]
+    fn test_folgezettel_should_follow_highest_top_level() {
+        let existing = ids(&["1", "2a", "10", "abc123", "3b1"]);
+
+        assert_eq!(next_folgezettel(&existing, None).unwrap(), "11");
+    }
+
+    #[test]
+    fn test_folgezettel_should_branch_with_letters_below_number() {
+        let existing = ids(&["1", "1a", "1b", "1b1", "12a"]);
+
+        assert_eq!(next_folgezettel(&existing, Some("1")).unwrap(), "1c");
+    }
+
+    #[test]
+    fn test_folgezettel_should_branch_with_numbers_below_letter() {
+        let existing = ids(&["1a", "1a1", "1a2b"]);
+
+        assert_eq!(next_folgezettel(&existing, Some("1a")).unwrap(), "1a3");
+    }
+
+    #[test]
+    fn test_folgezettel_should_roll_letters_over() {
+        let existing = ids(&["1z"]);
+
+        assert_eq!(next_folgezettel(&existing, Some("1")).unwrap(), "1aa");
+    }
+
+    #[test]
+    fn test_folgezettel_should_reject_invalid_parent() {
+        assert!(next_folgezettel(&[], Some("a1")).is_err());
+    }
+
+    #[test]
+    fn test_folgezettel_should_bump_last_segment() {
+        assert_eq!(bump_folgezettel("1a9", 2), "1a11");
+        assert_eq!(bump_folgezettel("1z", 1), "1aa");
+    }
+
+    #[test]
+    fn test_generator_should_suffix_repeated_slug() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let generator = NoteIdScheme::Slug
+            .generator(temp_dir.path(), "My Note", None)
+            .unwrap();
+
+        assert_eq!(generator(), "my-note");
+        assert_eq!(generator(), "my-note-2");
+        assert_eq!(generator(), "my-note-3");
+    }
+
+    #[test]
+    fn test_generator_should_move_folgezettel_to_next_sibling() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let generator = NoteIdScheme::Folgezettel
+            .generator(temp_dir.path(), "", Some("1"))
+            .unwrap();
+
+        assert_eq!(generator(), "1a");
+        assert_eq!(generator(), "1b");
+    }
+
+    #[test]
+    fn test_generator_should_number_folgezettel_after_notes_in_subdirectories() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        temp_dir.child("1.md").touch().unwrap();
+        temp_dir.child("lit/2.md").touch().unwrap();
+        let generator = NoteIdScheme::Folgezettel
+            .generator(temp_dir.path(), "", None)
+            .unwrap();
+
+        assert_eq!(generator(), "3");
+    }
+
+    #[test]
+    fn test_pattern_should_render_slug_and_date() {
+        let id = render_pattern("{{date \"%Y\"}}-{{slug title}}", "My Note").unwrap();
+
+        assert_eq!(id, format!("{}-my-note", chrono::Local::now().format("%Y")));
+    }
+
+    #[test]
+    fn test_pattern_should_reject_empty_id() {
+        assert!(render_pattern("{{slug title}}", "").is_err());
+    }
+
+    #[test]
+    fn test_scheme_should_deserialize_from_config() {
+        #[derive(Deserialize)]
+        struct Wrapper {
+            id_scheme: NoteIdScheme,
+        }
+
+        let plain: Wrapper = toml::from_str("id_scheme = \"timestamp\"").unwrap();
+        let pattern: Wrapper =
+            toml::from_str("id_scheme = { pattern = \"{{slug title}}\" }").unwrap();
+
+        assert_eq!(plain.id_scheme, NoteIdScheme::Timestamp);
+        assert_eq!(
+            pattern.id_scheme,
+            NoteIdScheme::Pattern(String::from("{{slug title}}"))
+        );
+    }
+}
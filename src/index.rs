@@ -0,0 +1,643 @@
+use crate::atomic;
+use crate::links::Link;
+use crate::vault::note_paths;
+use crate::ztr::{Error, Note, Result, Vault};
+use chrono::{DateTime, Local};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Write;
+use std::time::{Duration, SystemTime};
+use std::{fs, io, path};
+
+/// Bumped whenever the layout of the index changes, older indexes are rebuilt.
+const INDEX_VERSION: u32 = 3;
+
+/// Files modified this close to when their entry was written may have changed again within
+/// the same mtime tick, so their hash is checked too.
+const RACY_WINDOW: Duration = Duration::from_secs(2);
+
+/// Data about notes keyed by their path below the vault root, with what is needed to tell
+/// whether a file changed since.
+///
+/// On disk an index is a line with its version followed by one line per entry. Changed
+/// entries are appended as further lines that override earlier ones, and the file is only
+/// written anew once it holds more overridden lines than live ones.
+struct Index<T> {
+    entries: BTreeMap<path::PathBuf, IndexEntry<T>>,
+    /// Lines of the file that later lines override
+    stale: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Header {
+    version: u32,
+}
+
+/// A line of an index after the header. Without an entry the note at `path` is gone.
+#[derive(Serialize, Deserialize)]
+struct Record<E> {
+    path: path::PathBuf,
+    entry: Option<E>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct IndexEntry<T> {
+    /// Modification time in nanoseconds since the epoch
+    modified: u64,
+    size: u64,
+    hash: u64,
+    /// When the entry was written, in nanoseconds since the epoch
+    indexed: u64,
+    data: T,
+}
+
+/// The parts of a [`Note`] that come from its file, except for its body, which is read from
+/// the file when needed.
+#[derive(Serialize, Deserialize, Clone)]
+struct IndexedNote {
+    id: String,
+    title: String,
+    tags: Vec<String>,
+    created: Option<DateTime<Local>>,
+    vars: BTreeMap<String, Value>,
+    /// Byte offset of the body in the file
+    body_start: usize,
+    content_offset: usize,
+    /// Links with the start and end of their span
+    links: Vec<(Link, usize, usize)>,
+    /// The frontmatter as YAML, to keep its key order
+    frontmatter: String,
+}
+
+pub fn index_path(zk_root: &path::Path) -> path::PathBuf {
+    zk_root.join(".ztr").join("index")
+}
+
+/// Reads every note below `zk_root` like [`crate::ztr::Vault::load`], parsing only the files
+/// that changed since the index was last updated and then updating it. A missing or
+/// outdated index is rebuilt. Without `bodies` the `content` and `frontmatter` of every note
+/// are left empty and the files of unchanged notes are not read at all.
+pub fn load_vault(zk_root: &path::Path, bodies: bool) -> Result<Vault> {
+    let mut index = read_index(zk_root);
+    let now = nanos(SystemTime::now());
+    let mut updates = vec![];
+    let mut seen = BTreeSet::new();
+    let mut notes = vec![];
+    let mut invalid = vec![];
+
+    for note_path in note_paths(zk_root)? {
+        let key = note_path
+            .strip_prefix(zk_root)
+            .unwrap_or(&note_path)
+            .to_path_buf();
+        match load_note(&note_path, index.entries.get(&key), bodies, now) {
+            Ok((note, updated)) => {
+                if let Some(entry) = updated {
+                    updates.push(Record {
+                        path: key.clone(),
+                        entry: Some(entry),
+                    });
+                }
+                notes.push(note);
+                seen.insert(key);
+            }
+            // A note that cannot be read or parsed is reported, not fatal to the vault.
+            Err(e) => invalid.push((note_path, e)),
+        }
+    }
+
+    updates.extend(index.removed(&seen));
+    if !updates.is_empty() {
+        // The index is only a cache, a vault that cannot be written to is still read.
+        let _ = index.update(&index_path(zk_root), INDEX_VERSION, updates);
+    }
+    Ok(Vault {
+        root: zk_root.to_path_buf(),
+        notes,
+        invalid,
+    })
+}
+
+/// What `derive` makes of each note of `vault`, in the order of its notes. The results are
+/// cached in `.ztr/<name>` the way parsed notes are in the index, so only the notes that
+/// changed since are read and derived again. `version` is to be bumped whenever the layout
+/// of the results changes. A note that can no longer be read gets the default.
+pub fn load_derived<T>(
+    vault: &Vault,
+    name: &str,
+    version: u32,
+    derive: impl Fn(&Note) -> T,
+) -> Vec<T>
+where
+    T: Serialize + DeserializeOwned + Clone + Default,
+{
+    let file = vault.root.join(".ztr").join(name);
+    let mut index = Index::read(&file, version);
+    let now = nanos(SystemTime::now());
+    let mut updates = vec![];
+    let mut seen = BTreeSet::new();
+    let mut keys = vec![];
+
+    for note in &vault.notes {
+        // Notes that are not files have nothing to cache.
+        let Some(note_path) = &note.path else {
+            keys.push(Err(derive(note)));
+            continue;
+        };
+        let key = note_path
+            .strip_prefix(&vault.root)
+            .unwrap_or(note_path)
+            .to_path_buf();
+        let loaded = load_entry(
+            note_path,
+            index.entries.get(&key),
+            false,
+            now,
+            |raw, metadata| Ok(derive(&Note::from_file(note_path, raw, metadata)?)),
+        );
+        if let Ok((Cow::Owned(entry), _, _)) = loaded {
+            updates.push(Record {
+                path: key.clone(),
+                entry: Some(entry),
+            });
+        }
+        seen.insert(key.clone());
+        keys.push(Ok(key));
+    }
+
+    updates.extend(index.removed(&seen));
+    if !updates.is_empty() {
+        let _ = index.update(&file, version, updates);
+    }
+    keys.into_iter()
+        .map(|key| match key {
+            Ok(key) => index
+                .entries
+                .remove(&key)
+                .map(|entry| entry.data)
+                .unwrap_or_default(),
+            Err(derived) => derived,
+        })
+        .collect()
+}
+
+/// The note at `note_path`, from its `cached` index entry when that is still up to date,
+/// along with a new entry for the index when it is not.
+fn load_note(
+    note_path: &path::Path,
+    cached: Option<&IndexEntry<IndexedNote>>,
+    bodies: bool,
+    now: u64,
+) -> Result<(Note, Option<IndexEntry<IndexedNote>>)> {
+    let (entry, metadata, raw) = load_entry(note_path, cached, bodies, now, |raw, metadata| {
+        IndexedNote::from_note(&Note::from_file(note_path, raw, metadata)?, raw.len())
+    })?;
+    let content = raw.filter(|_| bodies).map(|raw| {
+        raw.get(entry.data.body_start..)
+            .unwrap_or_default()
+            .to_string()
+    });
+    let note = entry.data.to_note(note_path, &metadata, content)?;
+    match entry {
+        Cow::Borrowed(_) => Ok((note, None)),
+        Cow::Owned(entry) => Ok((note, Some(entry))),
+    }
+}
+
+/// The entry for the file at `file` with its metadata: `cached` when that is still up to
+/// date, or else one with what `parse` makes of the file's content. That content comes along
+/// whenever the file was read, which `read` makes sure of.
+fn load_entry<'a, T: Clone>(
+    file: &path::Path,
+    cached: Option<&'a IndexEntry<T>>,
+    read: bool,
+    now: u64,
+    parse: impl FnOnce(&str, &fs::Metadata) -> Result<T>,
+) -> Result<(Cow<'a, IndexEntry<T>>, fs::Metadata, Option<String>)> {
+    let metadata = fs::metadata(file)?;
+    let modified = metadata.modified().map(nanos).unwrap_or(0);
+    let fresh = cached.filter(|entry| {
+        entry.modified == modified
+            && entry.size == metadata.len()
+            && modified + (RACY_WINDOW.as_nanos() as u64) < entry.indexed
+    });
+    if let Some(entry) = fresh.filter(|_| !read) {
+        return Ok((Cow::Borrowed(entry), metadata, None));
+    }
+
+    let raw = fs::read_to_string(file)?;
+    let entry = match fresh.filter(|entry| entry.size == raw.len() as u64) {
+        Some(entry) => Cow::Borrowed(entry),
+        None => reindex(&raw, &metadata, cached, now, parse)?,
+    };
+    Ok((entry, metadata, Some(raw)))
+}
+
+/// The entry for `raw`, the content of a file, made by `parse` unless its hash shows the
+/// `cached` entry still holds. That entry is kept as is while writing it anew would leave it
+/// within [`RACY_WINDOW`] of its file's mtime all the same.
+fn reindex<'a, T: Clone>(
+    raw: &str,
+    metadata: &fs::Metadata,
+    cached: Option<&'a IndexEntry<T>>,
+    now: u64,
+    parse: impl FnOnce(&str, &fs::Metadata) -> Result<T>,
+) -> Result<Cow<'a, IndexEntry<T>>> {
+    let modified = metadata.modified().map(nanos).unwrap_or(0);
+    let size = raw.len() as u64;
+    let hash = fnv1a(raw.as_bytes());
+    match cached.filter(|entry| entry.hash == hash && entry.size == size) {
+        Some(entry)
+            if entry.modified == modified && modified + (RACY_WINDOW.as_nanos() as u64) >= now =>
+        {
+            Ok(Cow::Borrowed(entry))
+        }
+        Some(entry) => Ok(Cow::Owned(IndexEntry {
+            modified,
+            indexed: now,
+            ..entry.clone()
+        })),
+        None => Ok(Cow::Owned(IndexEntry {
+            modified,
+            size,
+            hash,
+            indexed: now,
+            data: parse(raw, metadata)?,
+        })),
+    }
+}
+
+impl IndexedNote {
+    /// `note`, parsed from a file `raw_len` bytes long.
+    fn from_note(note: &Note, raw_len: usize) -> Result<IndexedNote> {
+        let frontmatter = match note.frontmatter.is_empty() {
+            true => String::new(),
+            false => serde_yaml::to_string(&note.frontmatter)
+                .map_err(|e| Error::Frontmatter(e.to_string()))?,
+        };
+        Ok(IndexedNote {
+            id: note.id.clone(),
+            title: note.title.clone(),
+            tags: note.tags.clone(),
+            created: note.created,
+            vars: note.vars.clone(),
+            body_start: raw_len - note.content.len(),
+            content_offset: note.content_offset,
+            links: note
+                .links
+                .iter()
+                .map(|link| (link.clone(), link.span.start, link.span.end))
+                .collect(),
+            frontmatter,
+        })
+    }
+
+    /// The note at `note_path` with the body `content`. Without a body the frontmatter is
+    /// left empty as well, as both are only needed to write the note back.
+    fn to_note(
+        &self,
+        note_path: &path::Path,
+        metadata: &fs::Metadata,
+        content: Option<String>,
+    ) -> Result<Note> {
+        let frontmatter = match self.frontmatter.is_empty() || content.is_none() {
+            true => serde_yaml::Mapping::new(),
+            false => serde_yaml::from_str(&self.frontmatter)
+                .map_err(|e| Error::Frontmatter(e.to_string()))?,
+        };
+        Ok(Note {
+            vars: self.vars.clone(),
+            template: String::new(),
+            filename: note_path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            id: self.id.clone(),
+            created: self.created,
+            modified: metadata.modified().ok().map(DateTime::<Local>::from),
+            title: self.title.clone(),
+            content: content.unwrap_or_default(),
+            tags: self.tags.clone(),
+            path: Some(note_path.to_path_buf()),
+            content_offset: self.content_offset,
+            links: self
+                .links
+                .iter()
+                .map(|(link, start, end)| Link {
+                    span: *start..*end,
+                    ..link.clone()
+                })
+                .collect(),
+            frontmatter,
+        })
+    }
+}
+
+/// The index of `zk_root`, or an empty one when it is missing, unreadable or outdated.
+fn read_index(zk_root: &path::Path) -> Index<IndexedNote> {
+    Index::read(&index_path(zk_root), INDEX_VERSION)
+}
+
+#[cfg(test)]
+fn write_index(zk_root: &path::Path, index: &Index<IndexedNote>) -> Result<()> {
+    index.write(&index_path(zk_root), INDEX_VERSION)
+}
+
+impl<T: Serialize + DeserializeOwned> Index<T> {
+    /// The index in `file`, or an empty one when it is missing, unreadable or not of
+    /// `version`.
+    fn read(file: &path::Path, version: u32) -> Self {
+        let Ok(raw) = fs::read_to_string(file) else {
+            return Index::default();
+        };
+        let mut lines = raw.lines();
+        let found = lines
+            .next()
+            .and_then(|line| serde_json::from_str::<Header>(line).ok())
+            .map(|header| header.version);
+        if found != Some(version) {
+            return Index::default();
+        }
+
+        let mut index = Index::default();
+        let mut records = 0;
+        for line in lines {
+            let Ok(record) = serde_json::from_str::<Record<IndexEntry<T>>>(line) else {
+                return Index::default();
+            };
+            records += 1;
+            index.apply(record);
+        }
+        index.stale = records - index.entries.len();
+        index
+    }
+
+    /// Records removing the entries of the notes not in `seen`.
+    fn removed(&self, seen: &BTreeSet<path::PathBuf>) -> Vec<Record<IndexEntry<T>>> {
+        self.entries
+            .keys()
+            .filter(|key| !seen.contains(*key))
+            .map(|key| Record {
+                path: key.clone(),
+                entry: None,
+            })
+            .collect()
+    }
+
+    /// Applies `updates` to the index and to `file`, by appending them unless that would
+    /// leave more overridden lines than live ones.
+    fn update(
+        &mut self,
+        file: &path::Path,
+        version: u32,
+        updates: Vec<Record<IndexEntry<T>>>,
+    ) -> Result<()> {
+        if self.stale + updates.len() <= self.entries.len() {
+            let mut lines = vec![];
+            for record in &updates {
+                serde_json::to_writer(&mut lines, record).map_err(io::Error::from)?;
+                lines.push(b'\n');
+            }
+            self.stale += updates.len();
+            for record in updates {
+                self.apply(record);
+            }
+            let mut file = fs::OpenOptions::new().append(true).open(file)?;
+            file.write_all(&lines)?;
+            return Ok(());
+        }
+
+        for record in updates {
+            self.apply(record);
+        }
+        self.stale = 0;
+        self.write(file, version)
+    }
+
+    fn apply(&mut self, record: Record<IndexEntry<T>>) {
+        match record.entry {
+            Some(entry) => self.entries.insert(record.path, entry),
+            None => self.entries.remove(&record.path),
+        };
+    }
+
+    fn write(&self, file: &path::Path, version: u32) -> Result<()> {
+        if let Some(dir) = file.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut lines = serde_json::to_vec(&Header { version }).map_err(io::Error::from)?;
+        lines.push(b'\n');
+        for (path, entry) in &self.entries {
+            let record = Record {
+                path: path.clone(),
+                entry: Some(entry),
+            };
+            serde_json::to_writer(&mut lines, &record).map_err(io::Error::from)?;
+            lines.push(b'\n');
+        }
+        atomic::write(file, lines)?;
+        Ok(())
+    }
+}
+
+impl<T> Default for Index<T> {
+    fn default() -> Self {
+        Index {
+            entries: BTreeMap::new(),
+            stale: 0,
+        }
+    }
+}
+
+fn nanos(time: SystemTime) -> u64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .map(|since| since.as_nanos() as u64)
+        .unwrap_or(0)
+}
+
+/// 64-bit FNV-1a, stable across builds unlike the standard library's hasher.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::prelude::*;
+
+    fn age_index(zk_root: &path::Path) {
+        let mut index = read_index(zk_root);
+        for entry in index.entries.values_mut() {
+            entry.indexed += 10 * RACY_WINDOW.as_nanos() as u64;
+        }
+        write_index(zk_root, &index).unwrap();
+    }
+
+    #[test]
+    fn test_load_vault_should_write_index() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        temp_dir
+            .child("a.md")
+            .write_str("---\ntags: [x]\nsource: web\n---\n# Alpha\n[[b]]")
+            .unwrap();
+
+        let notes = load_vault(temp_dir.path(), true).unwrap().notes;
+
+        let index = read_index(temp_dir.path());
+        let entry = &index.entries[path::Path::new("a.md")];
+        assert_eq!(entry.data.title, "Alpha");
+        assert_eq!(entry.data.tags, vec!["x"]);
+        assert_eq!(entry.data.links[0].0.target, "b");
+        assert_eq!(
+            notes[0].links,
+            Note::from_path(&temp_dir.path().join("a.md"))
+                .unwrap()
+                .links
+        );
+    }
+
+    #[test]
+    fn test_load_vault_should_read_unchanged_notes_from_index() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        temp_dir.child("a.md").write_str("# Alpha").unwrap();
+        load_vault(temp_dir.path(), true).unwrap();
+        age_index(temp_dir.path());
+        let mut index = read_index(temp_dir.path());
+        let entry = index.entries.get_mut(path::Path::new("a.md")).unwrap();
+        entry.data.title = String::from("From index");
+        write_index(temp_dir.path(), &index).unwrap();
+
+        let notes = load_vault(temp_dir.path(), true).unwrap().notes;
+
+        assert_eq!(notes[0].title, "From index");
+        assert_eq!(notes[0].path, Some(temp_dir.path().join("a.md")));
+    }
+
+    #[test]
+    fn test_load_vault_should_reparse_changed_and_drop_removed_notes() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        temp_dir.child("a.md").write_str("# Alpha").unwrap();
+        temp_dir.child("b.md").write_str("# Beta").unwrap();
+        load_vault(temp_dir.path(), true).unwrap();
+        age_index(temp_dir.path());
+
+        temp_dir.child("a.md").write_str("# Changed").unwrap();
+        fs::remove_file(temp_dir.path().join("b.md")).unwrap();
+        let notes = load_vault(temp_dir.path(), true).unwrap().notes;
+
+        assert_eq!(notes.len(), 1);
+        assert_eq!(notes[0].title, "Changed");
+        assert_eq!(read_index(temp_dir.path()).entries.len(), 1);
+    }
+
+    #[test]
+    fn test_load_vault_should_read_bodies_from_files_only() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        temp_dir
+            .child("a.md")
+            .write_str("---\ntags: [x]\n---\n# Alpha\nsecret body")
+            .unwrap();
+        load_vault(temp_dir.path(), true).unwrap();
+        age_index(temp_dir.path());
+
+        let with_bodies = load_vault(temp_dir.path(), true).unwrap().notes;
+        let without_bodies = load_vault(temp_dir.path(), false).unwrap().notes;
+
+        let index = fs::read_to_string(index_path(temp_dir.path())).unwrap();
+        assert!(!index.contains("secret body"));
+        assert_eq!(with_bodies[0].content, "# Alpha\nsecret body");
+        assert_eq!(without_bodies[0].content, "");
+        assert_eq!(without_bodies[0].title, "Alpha");
+    }
+
+    #[test]
+    fn test_load_vault_should_append_changed_entries_to_index() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        temp_dir.child("a.md").write_str("# Alpha").unwrap();
+        temp_dir.child("b.md").write_str("# Beta").unwrap();
+        load_vault(temp_dir.path(), true).unwrap();
+        age_index(temp_dir.path());
+        let before = fs::read_to_string(index_path(temp_dir.path())).unwrap();
+
+        temp_dir.child("a.md").write_str("# Changed").unwrap();
+        load_vault(temp_dir.path(), false).unwrap();
+
+        let after = fs::read_to_string(index_path(temp_dir.path())).unwrap();
+        assert!(after.starts_with(&before));
+        assert_eq!(after.lines().count(), before.lines().count() + 1);
+        assert_eq!(
+            read_index(temp_dir.path()).entries[path::Path::new("a.md")]
+                .data
+                .title,
+            "Changed"
+        );
+    }
+
+    #[test]
+    fn test_load_vault_should_rewrite_index_with_more_stale_than_live_lines() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        temp_dir.child("a.md").write_str("# Alpha").unwrap();
+        load_vault(temp_dir.path(), false).unwrap();
+
+        temp_dir.child("a.md").write_str("# Changed").unwrap();
+        load_vault(temp_dir.path(), false).unwrap();
+        let appended = fs::read_to_string(index_path(temp_dir.path())).unwrap();
+        temp_dir.child("a.md").write_str("# Changed again").unwrap();
+        load_vault(temp_dir.path(), false).unwrap();
+
+        let rewritten = fs::read_to_string(index_path(temp_dir.path())).unwrap();
+        assert_eq!(appended.lines().count(), 3);
+        assert_eq!(rewritten.lines().count(), 2);
+        assert_eq!(read_index(temp_dir.path()).stale, 0);
+    }
+
+    #[test]
+    fn test_load_vault_should_report_unreadable_notes_and_keep_the_others() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        temp_dir.child("a.md").write_str("# Alpha").unwrap();
+        temp_dir.child("b.md").write_binary(b"# B\xff\xfe").unwrap();
+
+        let vault = load_vault(temp_dir.path(), true).unwrap();
+
+        assert_eq!(vault.notes.len(), 1);
+        assert_eq!(vault.invalid.len(), 1);
+        assert_eq!(vault.invalid[0].0, temp_dir.path().join("b.md"));
+        assert!(matches!(vault.invalid[0].1, Error::Io(_)));
+    }
+
+    #[test]
+    fn test_load_vault_should_keep_frontmatter_order() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        temp_dir
+            .child("a.md")
+            .write_str("---\nz: 1\ntitle: Alpha\na: 2\n---\nbody")
+            .unwrap();
+        load_vault(temp_dir.path(), true).unwrap();
+        age_index(temp_dir.path());
+
+        let notes = load_vault(temp_dir.path(), true).unwrap().notes;
+
+        assert_eq!(
+            notes[0].to_markdown().unwrap(),
+            "---\nz: 1\ntitle: Alpha\na: 2\n---\nbody"
+        );
+    }
+
+    #[test]
+    fn test_load_vault_should_rebuild_corrupt_index() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        temp_dir.child("a.md").write_str("# Alpha").unwrap();
+        temp_dir.child(".ztr/index").write_str("{not json").unwrap();
+
+        let notes = load_vault(temp_dir.path(), true).unwrap().notes;
+
+        assert_eq!(notes[0].title, "Alpha");
+        assert_eq!(read_index(temp_dir.path()).entries.len(), 1);
+    }
+}
@@ -0,0 +1,375 @@
+use crate::rename::{write_changes, FileChange};
+use crate::templates;
+use crate::ztr::{Config, Error, NewNote, Note, Result, Vault};
+use chrono::{Datelike, Days, Months, NaiveDate, Weekday};
+use serde_derive::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::{fs, path};
+
+/// Template for journal entries when the vault has neither a template named after the period
+/// nor a `journal` template, and the config sets none.
+const JOURNAL_TEMPLATE: &str = "# {{title}}\n\n{{#each tags}}#[[{{this}}]] {{/each}}\n\n{{#if prev}}Previous: [[{{prev}}]]\n{{/if}}{{#if next}}Next: [[{{next}}]]\n{{/if}}";
+
+/// Tag every journal entry starts with.
+const JOURNAL_TAG: &str = "journal";
+
+/// How much time one journal entry covers.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Period {
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+/// Settings of journal entries from the `[journal]` config table.
+#[derive(Deserialize, Default, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct JournalConfig {
+    /// Directory below the vault root new entries are created in
+    pub dir: Option<path::PathBuf>,
+    /// Template for entries, by name or inline, when there is no template named after the period
+    pub template: Option<String>,
+}
+
+impl Period {
+    pub fn name(self) -> &'static str {
+        match self {
+            Period::Daily => "daily",
+            Period::Weekly => "weekly",
+            Period::Monthly => "monthly",
+        }
+    }
+
+    /// First day of the period holding `date`: the day itself, its week's Monday or the first
+    /// of its month.
+    pub fn start(self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Period::Daily => date,
+            Period::Weekly => date - Days::new(date.weekday().num_days_from_monday().into()),
+            Period::Monthly => date.with_day(1).unwrap_or(date),
+        }
+    }
+
+    /// Last day of the period holding `date`.
+    pub fn end(self, date: NaiveDate) -> NaiveDate {
+        let start = self.start(date);
+        match self {
+            Period::Daily => start,
+            Period::Weekly => start + Days::new(6),
+            Period::Monthly => start + Months::new(1) - Days::new(1),
+        }
+    }
+
+    /// ID of the entry for `date`, such as `2026-10-15`, `2026-W42` or `2026-10`.
+    pub fn id(self, date: NaiveDate) -> String {
+        match self {
+            Period::Daily => date.format("%Y-%m-%d").to_string(),
+            Period::Weekly => {
+                let week = date.iso_week();
+                format!("{}-W{:02}", week.year(), week.week())
+            }
+            Period::Monthly => date.format("%Y-%m").to_string(),
+        }
+    }
+
+    /// First day of the period of the entry with the ID `id`, if it is one.
+    pub fn parse_id(self, id: &str) -> Option<NaiveDate> {
+        let date = match self {
+            Period::Daily => NaiveDate::parse_from_str(id, "%Y-%m-%d").ok()?,
+            Period::Weekly => {
+                let (year, week) = id.split_once("-W")?;
+                NaiveDate::from_isoywd_opt(year.parse().ok()?, week.parse().ok()?, Weekday::Mon)?
+            }
+            Period::Monthly => {
+                let (year, month) = id.split_once('-')?;
+                NaiveDate::from_ymd_opt(year.parse().ok()?, month.parse().ok()?, 1)?
+            }
+        };
+        (self.id(date) == id).then_some(date)
+    }
+
+    fn title(self, date: NaiveDate) -> String {
+        match self {
+            Period::Daily => date.format("%A, %-d %B %Y").to_string(),
+            Period::Weekly => {
+                let week = date.iso_week();
+                format!("Week {} of {}", week.week(), week.year())
+            }
+            Period::Monthly => date.format("%B %Y").to_string(),
+        }
+    }
+}
+
+/// The nearest existing entries of `period` before and after the one holding `date`.
+pub fn neighbours(
+    vault: &Vault,
+    period: Period,
+    date: NaiveDate,
+) -> (Option<&Note>, Option<&Note>) {
+    let start = period.start(date);
+    let mut prev: Option<(NaiveDate, &Note)> = None;
+    let mut next: Option<(NaiveDate, &Note)> = None;
+    for note in &vault.notes {
+        let Some(entry) = period.parse_id(&note.id) else {
+            continue;
+        };
+        if entry < start && prev.is_none_or(|(prev, _)| entry > prev) {
+            prev = Some((entry, note));
+        }
+        if entry > start && next.is_none_or(|(next, _)| entry < next) {
+            next = Some((entry, note));
+        }
+    }
+    (prev.map(|(_, note)| note), next.map(|(_, note)| note))
+}
+
+/// Path of the entry of `period` holding `date`. A missing entry is created from the
+/// journal template, which gets the `period`, its `start` and `end` dates and the IDs of
+/// the `prev` and `next` existing entries to link to. Those entries get a `Next:` and a
+/// `Previous:` link back to the new one.
+pub fn open_entry(
+    vault: &Vault,
+    period: Period,
+    date: NaiveDate,
+    config: &Config,
+) -> Result<path::PathBuf> {
+    let id = period.id(date);
+    if let Some(existing) = vault.notes.iter().find(|note| note.id == id) {
+        return existing
+            .path
+            .clone()
+            .ok_or_else(|| Error::NotFound(format!("note '{}' has no file", id)));
+    }
+
+    let templates = templates::load(&vault.root)?;
+    let template = match &config.journal.template {
+        _ if templates.contains_key(period.name()) => period.name(),
+        Some(template) => template.as_str(),
+        None if templates.contains_key("journal") => "journal",
+        None => JOURNAL_TEMPLATE,
+    };
+
+    let (prev, next) = neighbours(vault, period, date);
+    let neighbour = |note: Option<&Note>| note.map_or(Value::Null, |note| note.id.clone().into());
+    let vars = BTreeMap::from([
+        (String::from("period"), period.name().into()),
+        (String::from("start"), period.start(date).to_string().into()),
+        (String::from("end"), period.end(date).to_string().into()),
+        (String::from("prev"), neighbour(prev)),
+        (String::from("next"), neighbour(next)),
+    ]);
+    let note = NewNote {
+        vars,
+        ..NewNote::new(
+            Some(template.to_string()),
+            Some(period.title(date)),
+            Some(String::new()),
+            Some(vec![String::from(JOURNAL_TAG)]),
+        )
+    };
+
+    let name = match &config.journal.dir {
+        Some(dir) => {
+            fs::create_dir_all(vault.root.join(dir))?;
+            format!("{}/{}", dir.to_string_lossy(), id)
+        }
+        None => id.clone(),
+    };
+    let entry = crate::open_create(&vault.root, &|| name.clone(), &note, &config.note)?;
+
+    let mut changes = vec![];
+    for (neighbour, label) in [(prev, "Next"), (next, "Previous")] {
+        if let Some(change) = neighbour
+            .map(|note| link_back(note, label, &id))
+            .transpose()?
+        {
+            changes.extend(change);
+        }
+    }
+    write_changes(&changes)?;
+    Ok(entry)
+}
+
+/// Change pointing the `label: [[..]]` line of the entry `note` at `id`, or adding one when
+/// it has none. `None` when the entry already links to `id`.
+fn link_back(note: &Note, label: &str, id: &str) -> Result<Option<FileChange>> {
+    let Some(note_path) = &note.path else {
+        return Ok(None);
+    };
+    let before = fs::read_to_string(note_path)?;
+    let link = format!("{}: [[{}]]", label, id);
+    if before.contains(&format!("[[{}]]", id)) {
+        return Ok(None);
+    }
+
+    let prefix = format!("{}: [[", label);
+    let mut after = String::with_capacity(before.len() + link.len() + 1);
+    let mut replaced = false;
+    for line in before.split_inclusive('\n') {
+        if !replaced && line.starts_with(&prefix) {
+            after.push_str(&link);
+            after.push_str(&line[line.trim_end().len()..]);
+            replaced = true;
+        } else {
+            after.push_str(line);
+        }
+    }
+    if !replaced {
+        if !after.is_empty() && !after.ends_with('\n') {
+            after.push('\n');
+        }
+        after.push_str(&link);
+        after.push('\n');
+    }
+    Ok(Some(FileChange {
+        path: note_path.clone(),
+        before,
+        after,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::prelude::*;
+
+    fn date(raw: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(raw, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn test_id_should_derive_from_date_and_parse_back() {
+        let day = date("2026-10-15");
+
+        let ids: Vec<String> = [Period::Daily, Period::Weekly, Period::Monthly]
+            .iter()
+            .map(|period| period.id(day))
+            .collect();
+
+        assert_eq!(ids, vec!["2026-10-15", "2026-W42", "2026-10"]);
+        assert_eq!(Period::Daily.parse_id("2026-10-15"), Some(day));
+        assert_eq!(
+            Period::Weekly.parse_id("2026-W42"),
+            Some(date("2026-10-12"))
+        );
+        assert_eq!(
+            Period::Monthly.parse_id("2026-10"),
+            Some(date("2026-10-01"))
+        );
+        assert_eq!(Period::Daily.parse_id("2026-1-5"), None);
+        assert_eq!(Period::Monthly.parse_id("2026-10-15"), None);
+    }
+
+    #[test]
+    fn test_end_should_close_the_period() {
+        assert_eq!(Period::Weekly.end(date("2026-10-15")), date("2026-10-18"));
+        assert_eq!(Period::Monthly.end(date("2026-02-10")), date("2026-02-28"));
+        assert_eq!(Period::Weekly.id(date("2027-01-01")), "2026-W53");
+    }
+
+    #[test]
+    fn test_open_entry_should_create_entry_linking_neighbours() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        temp_dir.child("2026-10-12.md").write_str("# Mon").unwrap();
+        temp_dir.child("2026-10-20.md").write_str("# Tue").unwrap();
+        temp_dir.child("2026-10.md").write_str("# October").unwrap();
+        let vault = Vault::load(temp_dir.path()).unwrap();
+
+        let entry = open_entry(
+            &vault,
+            Period::Daily,
+            date("2026-10-15"),
+            &Config::default(),
+        );
+
+        assert_eq!(entry.unwrap(), temp_dir.path().join("2026-10-15.md"));
+        temp_dir.child("2026-10-15.md").assert(
+            "# Thursday, 15 October 2026\n\n#[[journal]] \n\nPrevious: [[2026-10-12]]\nNext: [[2026-10-20]]\n",
+        );
+        temp_dir
+            .child("2026-10-12.md")
+            .assert("# Mon\nNext: [[2026-10-15]]\n");
+        temp_dir
+            .child("2026-10-20.md")
+            .assert("# Tue\nPrevious: [[2026-10-15]]\n");
+    }
+
+    #[test]
+    fn test_open_entry_should_point_neighbour_links_at_new_entry() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        temp_dir
+            .child("2026-10-12.md")
+            .write_str("# Mon\n\nNext: [[2026-10-20]]\n")
+            .unwrap();
+        temp_dir
+            .child("2026-10-20.md")
+            .write_str("# Tue\n\nPrevious: [[2026-10-12]]\nSee [[2026-10-15]]\n")
+            .unwrap();
+        let vault = Vault::load(temp_dir.path()).unwrap();
+
+        open_entry(
+            &vault,
+            Period::Daily,
+            date("2026-10-15"),
+            &Config::default(),
+        )
+        .unwrap();
+
+        temp_dir
+            .child("2026-10-12.md")
+            .assert("# Mon\n\nNext: [[2026-10-15]]\n");
+        temp_dir
+            .child("2026-10-20.md")
+            .assert("# Tue\n\nPrevious: [[2026-10-12]]\nSee [[2026-10-15]]\n");
+        let vault = Vault::load(temp_dir.path()).unwrap();
+        let issues = crate::check::check(&vault);
+        assert!(!issues
+            .iter()
+            .any(|issue| matches!(issue, crate::check::Issue::Orphan { .. })));
+    }
+
+    #[test]
+    fn test_open_entry_should_return_existing_entry() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        temp_dir
+            .child("journal/2026-10.md")
+            .write_str("# Mine")
+            .unwrap();
+        let vault = Vault::load(temp_dir.path()).unwrap();
+
+        let entry = open_entry(
+            &vault,
+            Period::Monthly,
+            date("2026-10-15"),
+            &Config::default(),
+        );
+
+        assert_eq!(entry.unwrap(), temp_dir.path().join("journal/2026-10.md"));
+        temp_dir.child("journal/2026-10.md").assert("# Mine");
+    }
+
+    #[test]
+    fn test_open_entry_should_use_period_template_and_dir() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        temp_dir
+            .child(".ztr/templates/weekly.hbs")
+            .write_str("# {{title}} ({{start}} to {{end}}){{#if prev}} after [[{{prev}}]]{{/if}}")
+            .unwrap();
+        let vault = Vault::load(temp_dir.path()).unwrap();
+        let config = Config {
+            journal: JournalConfig {
+                dir: Some(path::PathBuf::from("journal")),
+                template: Some(String::from("unused")),
+            },
+            ..Config::default()
+        };
+
+        let entry = open_entry(&vault, Period::Weekly, date("2026-10-15"), &config);
+
+        assert_eq!(entry.unwrap(), temp_dir.path().join("journal/2026-W42.md"));
+        temp_dir
+            .child("journal/2026-W42.md")
+            .assert("# Week 42 of 2026 (2026-10-12 to 2026-10-18)");
+    }
+}
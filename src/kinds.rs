@@ -0,0 +1,313 @@
+use crate::note::{split_frontmatter, FRONTMATTER_FENCE, KNOWN_KEYS};
+use crate::query::duration_days;
+use crate::rename::{plan_move, FileChange, Move};
+use crate::tags::remove_inline_tags;
+use crate::templates;
+use crate::ztr::{Config, Error, Note, Result, Vault};
+use chrono::{Duration, Local};
+use serde_derive::Deserialize;
+use serde_yaml::{Mapping, Value as Yaml};
+use std::{fs, path};
+
+/// Kinds every vault has, in the order notes usually move through them.
+pub const BUILTIN_KINDS: &[&str] = &["fleeting", "literature", "permanent"];
+
+/// How old a fleeting note gets before `ztr inbox` lists it, unless `inbox_age` is set.
+pub const DEFAULT_INBOX_AGE: &str = "7d";
+
+/// Settings of a kind of note from the `[kinds.<name>]` config table.
+#[derive(Deserialize, Default, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct NoteKind {
+    /// Template for notes of this kind, by name or inline, instead of `note.template`
+    pub template: Option<String>,
+    /// Directory below the vault root that notes of this kind are kept in
+    pub dir: Option<path::PathBuf>,
+}
+
+/// The frontmatter `kind` of `note`, or else the first of its tags naming a kind, such as
+/// the `fleeting` tag of the default template.
+pub fn note_kind<'a>(note: &'a Note, config: &Config) -> Option<&'a str> {
+    note.vars
+        .get("kind")
+        .and_then(|kind| kind.as_str())
+        .or_else(|| {
+            note.tags
+                .iter()
+                .find(|tag| config.is_kind(tag))
+                .map(String::as_str)
+        })
+}
+
+/// `age`, such as `12h`, `7d`, `2w` or `1y`, as a duration.
+pub fn parse_age(age: &str) -> Option<Duration> {
+    duration_days(age).map(|days| Duration::seconds((days * 24.0 * 60.0 * 60.0) as i64))
+}
+
+/// Fleeting notes created more than `older_than` ago, oldest first. Without `older_than`
+/// the `inbox_age` of the config is used.
+pub fn inbox<'a>(
+    vault: &'a Vault,
+    config: &Config,
+    older_than: Option<Duration>,
+) -> Result<Vec<&'a Note>> {
+    let age = match older_than {
+        Some(age) => age,
+        None => {
+            let age = config.inbox_age.as_deref().unwrap_or(DEFAULT_INBOX_AGE);
+            parse_age(age).ok_or_else(|| {
+                Error::Config(format!("inbox_age '{}' is not an age such as 7d", age))
+            })?
+        }
+    };
+    let cutoff = Local::now() - age;
+
+    let mut notes: Vec<&Note> = vault
+        .notes
+        .iter()
+        .filter(|note| note_kind(note, config) == Some("fleeting"))
+        .filter(|note| note.created.is_some_and(|created| created < cutoff))
+        .collect();
+    notes.sort_by_key(|note| note.created);
+    Ok(notes)
+}
+
+/// Plans turning `note` into a note of the kind `to`. Its frontmatter gets `kind: <to>`
+/// along with the keys the kind's template renders that the note lacks, tags naming a kind
+/// are dropped, and the note moves into the kind's directory with links to it rewritten.
+pub fn plan_promote(vault: &Vault, note: &Note, to: &str, config: &Config) -> Result<Move> {
+    let kind = config.kind(to)?;
+    let Some(note_path) = &note.path else {
+        return Err(Error::NotFound(format!("note '{}' has no file", note.id)));
+    };
+
+    let dir = kind.dir.as_ref().map(|dir| vault.root.join(dir));
+    let mut planned = match dir.filter(|dir| note_path.parent() != Some(dir.as_path())) {
+        Some(dir) => {
+            let new_name = dir.join(&note.filename);
+            let new_name = new_name.strip_prefix(&vault.root).unwrap_or(&new_name);
+            plan_move(vault, note, &new_name.to_string_lossy(), None)?
+        }
+        None => {
+            let raw = fs::read_to_string(note_path)?;
+            Move {
+                from: note_path.clone(),
+                to: note_path.clone(),
+                changes: vec![FileChange {
+                    path: note_path.clone(),
+                    before: raw.clone(),
+                    after: raw,
+                }],
+            }
+        }
+    };
+
+    let promoted = &mut planned.changes[0];
+    promoted.after = promote(&vault.root, note, &promoted.after, to, &kind, config)?;
+    Ok(planned)
+}
+
+/// `raw`, the file of `note`, as a note of the kind `to`.
+fn promote(
+    zk_root: &path::Path,
+    note: &Note,
+    raw: &str,
+    to: &str,
+    kind: &NoteKind,
+    config: &Config,
+) -> Result<String> {
+    let mut promoted = Note::parse(&note.filename, raw)?;
+    promoted.created = note.created;
+    promoted.content = remove_inline_tags(&promoted.content, &|tag| config.is_kind(tag));
+    promoted.tags.retain(|tag| !config.is_kind(tag));
+
+    if let Some(template) = &kind.template {
+        let mut rendered = Note {
+            template: template.clone(),
+            ..promoted.clone()
+        };
+        let output = crate::render_note_template(&mut rendered, &templates::load(zk_root)?)?;
+        let (frontmatter, _) = split_frontmatter(&output)?;
+        for (key, value) in frontmatter {
+            let Some(name) = key.as_str().filter(|key| !KNOWN_KEYS.contains(key)) else {
+                continue;
+            };
+            if promoted.frontmatter.contains_key(&key) {
+                continue;
+            }
+            let json = serde_json::to_value(&value)
+                .map_err(|e| Error::Frontmatter(format!("{}: {}", name, e)))?;
+            promoted.vars.insert(name.to_string(), json);
+            promoted.frontmatter.insert(key, value);
+        }
+    }
+
+    promoted.vars.insert(String::from("kind"), to.into());
+    promoted.frontmatter.insert("kind".into(), to.into());
+    promoted.to_markdown()
+}
+
+/// `raw` with `kind: <kind>` added to the top of its frontmatter, unless it has a kind.
+pub fn with_kind(raw: &str, kind: &str) -> Result<String> {
+    let (frontmatter, content) = split_frontmatter(raw)?;
+    if frontmatter.contains_key("kind") {
+        return Ok(raw.to_string());
+    }
+    let mut line = Mapping::new();
+    line.insert("kind".into(), Yaml::from(kind));
+    let yaml = serde_yaml::to_string(&line).map_err(|e| Error::Frontmatter(e.to_string()))?;
+
+    match raw.len() - content.len() {
+        0 => Ok(format!(
+            "{fence}\n{yaml}{fence}\n{raw}",
+            fence = FRONTMATTER_FENCE
+        )),
+        _ => {
+            let fence_end = raw.find('\n').map_or(raw.len(), |end| end + 1);
+            Ok(format!(
+                "{}{}{}",
+                &raw[..fence_end],
+                yaml,
+                &raw[fence_end..]
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::prelude::*;
+
+    fn config(kinds: &str) -> Config {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let config_file = temp_dir.child("ztr.toml");
+        config_file.write_str(kinds).unwrap();
+        crate::config::load(&[config_file.to_path_buf()]).unwrap()
+    }
+
+    fn vault() -> (assert_fs::TempDir, Vault) {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        temp_dir
+            .child("a.md")
+            .write_str(
+                "---\ncreated: 2026-01-05T10:00:00+00:00\n---\n# Alpha\n\n#[[fleeting]] #[[idea]] ",
+            )
+            .unwrap();
+        temp_dir
+            .child("b.md")
+            .write_str(
+                "---\ncreated: 2025-12-20T10:00:00+00:00\nkind: fleeting\n---\n# Beta\n[[a]]",
+            )
+            .unwrap();
+        temp_dir
+            .child("c.md")
+            .write_str("---\nkind: permanent\ntags: [fleeting]\n---\n# Gamma")
+            .unwrap();
+        temp_dir
+            .child("d.md")
+            .write_str("# Delta\n[Beta](b.md)\n#[[fleeting]]")
+            .unwrap();
+
+        let vault = Vault::load(temp_dir.path()).unwrap();
+        (temp_dir, vault)
+    }
+
+    #[test]
+    fn test_note_kind_should_prefer_frontmatter_over_tags() {
+        let (_temp_dir, vault) = vault();
+        let config = Config::default();
+
+        let kinds: Vec<Option<&str>> = vault
+            .notes
+            .iter()
+            .map(|note| note_kind(note, &config))
+            .collect();
+
+        assert_eq!(
+            kinds,
+            vec![
+                Some("fleeting"),
+                Some("fleeting"),
+                Some("permanent"),
+                Some("fleeting")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_inbox_should_list_old_fleeting_notes_oldest_first() {
+        let (_temp_dir, vault) = vault();
+        let config = Config::default();
+
+        let notes = inbox(&vault, &config, None).unwrap();
+
+        let ids: Vec<&str> = notes.iter().map(|note| note.id.as_str()).collect();
+        assert_eq!(ids, vec!["b", "a"]);
+        assert!(inbox(&vault, &config, parse_age("100y"))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_inbox_should_reject_invalid_configured_age() {
+        let (_temp_dir, vault) = vault();
+        let config = config("inbox_age = \"soon\"\n");
+
+        let result = inbox(&vault, &config, None);
+
+        assert!(matches!(result, Err(Error::Config(_))));
+    }
+
+    #[test]
+    fn test_plan_promote_should_set_kind_and_drop_kind_tags() {
+        let (_temp_dir, vault) = vault();
+        let config = Config::default();
+        let note = vault.resolve("a").unwrap();
+
+        let planned = plan_promote(&vault, note, "permanent", &config).unwrap();
+
+        assert_eq!(planned.from, planned.to);
+        assert_eq!(
+            planned.changes[0].after,
+            "---\ncreated: 2026-01-05T10:00:00+00:00\nkind: permanent\n---\n# Alpha\n\n#[[idea]] "
+        );
+    }
+
+    #[test]
+    fn test_plan_promote_should_render_template_keys_and_move_into_dir() {
+        let (temp_dir, vault) = vault();
+        temp_dir
+            .child(".ztr/templates/literature.hbs")
+            .write_str("+++\n[vars]\nsource = \"unknown\"\n+++\n---\nsource: {{source}}\nkind: ignored\n---\n# {{title}}")
+            .unwrap();
+        let config = config("[kinds.literature]\ntemplate = \"literature\"\ndir = \"lit\"\n");
+        let note = vault.resolve("b").unwrap();
+
+        let planned = plan_promote(&vault, note, "literature", &config).unwrap();
+        planned.apply().unwrap();
+
+        assert!(!temp_dir.child("b.md").exists());
+        let moved = fs::read_to_string(temp_dir.path().join("lit/b.md")).unwrap();
+        assert!(moved.contains("kind: literature\nsource: unknown\n"));
+        temp_dir
+            .child("d.md")
+            .assert("# Delta\n[Beta](lit/b.md)\n#[[fleeting]]");
+    }
+
+    #[test]
+    fn test_with_kind_should_add_kind_to_frontmatter() {
+        assert_eq!(
+            with_kind("# A", "fleeting").unwrap(),
+            "---\nkind: fleeting\n---\n# A"
+        );
+        assert_eq!(
+            with_kind("---\ntags: [x]\n---\n# A", "fleeting").unwrap(),
+            "---\nkind: fleeting\ntags: [x]\n---\n# A"
+        );
+        assert_eq!(
+            with_kind("---\nkind: permanent\n---\n", "fleeting").unwrap(),
+            "---\nkind: permanent\n---\n"
+        );
+    }
+}
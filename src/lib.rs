@@ -1,67 +1,236 @@
+use chrono::SubsecRound;
 use handlebars::Handlebars;
-use rand::Rng;
+use std::collections::BTreeMap;
 use std::io;
 use std::io::Write;
-use std::iter;
-use std::{error, fs, path};
-use ztr::{NewNote, Note};
+use std::{fs, path};
+use ztr::{Error, NewNote, Note, Result};
+
+mod atomic;
+mod check;
+mod config;
+mod editor;
+mod error;
+mod graph;
+mod helpers;
+mod id;
+mod index;
+mod journal;
+mod kinds;
+mod links;
+mod note;
+mod query;
+mod rename;
+mod search;
+mod tags;
+mod templates;
+mod trash;
+mod vault;
+
+const MAX_CREATE_ATTEMPTS: usize = 10;
+const INLINE_TEMPLATE: &str = "ztr-inline-note";
 
 fn open_create(
     zk_root: &path::Path,
     name_generator: &dyn Fn() -> String,
     note: &NewNote,
     default: &ztr::DefaultNote,
-) -> io::Result<path::PathBuf> {
-    let note_name = name_generator() + ".md";
-    let note_path = zk_root.join(&note_name);
-    let mut file = fs::File::create(&note_path)?;
-
-    let resolved_note = resolve_note_defaults(&note_name, note, default);
-
-    let content = render_note_template(&resolved_note).unwrap_or(String::from(""));
+) -> Result<path::PathBuf> {
+    let (note_name, note_path, mut file) = create_new_note_file(zk_root, name_generator)?;
+
+    let mut resolved_note = resolve_note_defaults(&note_name, note, default);
+
+    let content = match templates::load(zk_root)
+        .and_then(|templates| render_note_template(&mut resolved_note, &templates))
+        .and_then(|content| match &note.kind {
+            Some(kind) => kinds::with_kind(&content, kind),
+            None => Ok(content),
+        }) {
+        Ok(content) => content,
+        Err(e) => {
+            drop(file);
+            fs::remove_file(&note_path)?;
+            return Err(e);
+        }
+    };
     write!(file, "{}", &content)?;
 
     Ok(note_path.to_path_buf())
 }
 
+/// Creates the note file exclusively, asking `name_generator` for another name while
+/// the previous one is already taken. Names may start with a directory below `zk_root`,
+/// and a name is taken when a note with its ID exists anywhere in the vault.
+fn create_new_note_file(
+    zk_root: &path::Path,
+    name_generator: &dyn Fn() -> String,
+) -> Result<(String, path::PathBuf, fs::File)> {
+    let taken = id::note_ids(zk_root)?;
+    let mut note_name = String::new();
+    for _ in 0..MAX_CREATE_ATTEMPTS {
+        note_name = name_generator() + ".md";
+        let id = note_name.rsplit('/').next().unwrap_or(&note_name);
+        if taken
+            .iter()
+            .any(|taken| Some(taken.as_str()) == id.strip_suffix(".md"))
+        {
+            continue;
+        }
+        let note_path = zk_root.join(&note_name);
+        match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&note_path)
+        {
+            Ok(file) => return Ok((note_name, note_path, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    Err(Error::Collision {
+        root: zk_root.to_path_buf(),
+        name: note_name,
+        attempts: MAX_CREATE_ATTEMPTS,
+    })
+}
+
 fn resolve_note_defaults(name: &str, note: &NewNote, default: &ztr::DefaultNote) -> Note {
+    let name = name.rsplit('/').next().unwrap_or(name);
+    let mut vars = note.vars.clone();
+    if let Some(kind) = &note.kind {
+        vars.insert(String::from("kind"), kind.as_str().into());
+    }
     Note {
         template: note.template.clone().unwrap_or(default.template.clone()),
         filename: name.to_string(),
+        id: name.strip_suffix(".md").unwrap_or(name).to_string(),
+        created: Some(chrono::Local::now().trunc_subsecs(0)),
+        modified: None,
         title: note.title.clone().unwrap_or(default.title.clone()),
         content: note.content.clone().unwrap_or(default.content.clone()),
         tags: note.tags.clone().unwrap_or(default.tags.clone()),
+        vars,
+        path: None,
+        content_offset: 0,
+        links: vec![],
+        frontmatter: serde_yaml::Mapping::new(),
     }
 }
 
-fn render_note_template(note: &Note) -> Result<String, Box<dyn error::Error>> {
+/// Builds the registry for rendering `template`, which names one of the vault's templates
+/// or is an inline template itself. Returns the registered name and the variable defaults.
+fn note_registry(
+    template: &str,
+    templates: &templates::Templates,
+) -> Result<(
+    Handlebars<'static>,
+    String,
+    BTreeMap<String, serde_json::Value>,
+)> {
     let mut hb = Handlebars::new();
-    hb.register_template_string("note", &note.template)?;
+    helpers::register(&mut hb);
+    templates::register(&mut hb, templates)?;
+
+    let (name, mut defaults) = match templates.get(template) {
+        Some(source) => (template.to_string(), source.defaults.clone()),
+        None => {
+            let source = templates::TemplateSource::parse(INLINE_TEMPLATE, template)?;
+            hb.register_template_string(INLINE_TEMPLATE, &source.body)?;
+            (INLINE_TEMPLATE.to_string(), source.defaults)
+        }
+    };
+
+    let partials = templates::referenced_vars(&hb, &name).partials;
+    for partial in partials.iter().filter_map(|partial| templates.get(partial)) {
+        for (var, default) in &partial.defaults {
+            defaults.entry(var.clone()).or_insert(default.clone());
+        }
+    }
 
-    let output = hb.render("note", &note).unwrap().to_string();
+    Ok((hb, name, defaults))
+}
 
-    Ok(output)
+/// Template variables of `name` that are neither note fields nor in `vars`.
+fn unsupplied_vars(
+    hb: &Handlebars,
+    name: &str,
+    defaults: &BTreeMap<String, serde_json::Value>,
+    vars: &BTreeMap<String, serde_json::Value>,
+) -> Vec<ztr::TemplateVar> {
+    templates::referenced_vars(hb, name)
+        .vars
+        .into_iter()
+        .filter(|var| !ztr::NOTE_FIELDS.contains(&var.as_str()) && !vars.contains_key(var))
+        .map(|var| ztr::TemplateVar {
+            default: defaults.get(&var).cloned(),
+            name: var,
+        })
+        .collect()
 }
 
-fn generate() -> String {
-    let len = 10;
-    const CHARSET: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
-    let mut rng = rand::thread_rng();
-    let one_char = || CHARSET[rng.gen_range(0..CHARSET.len())] as char;
-    iter::repeat_with(one_char).take(len).collect()
+/// Renders the named template `note.template` from the vault's templates,
+/// or `note.template` itself as an inline template when no template has that name.
+/// Variables the template needs but `note` lacks get their declared default, or fail.
+fn render_note_template(note: &mut Note, templates: &templates::Templates) -> Result<String> {
+    let (hb, name, defaults) = note_registry(&note.template, templates)?;
+
+    let mut missing = vec![];
+    for var in unsupplied_vars(&hb, &name, &defaults, &note.vars) {
+        match var.default {
+            Some(default) => {
+                note.vars.insert(var.name, default);
+            }
+            None => missing.push(var.name),
+        }
+    }
+    if !missing.is_empty() {
+        return Err(Error::MissingVariables(missing));
+    }
+
+    let output = hb.render(&name, &note)?;
+
+    Ok(output)
 }
 
 pub mod ztr {
-    use serde_derive::Serialize;
+    use serde_derive::{Deserialize, Serialize};
 
     use super::*;
     use std::path;
 
+    pub use crate::check::{check, Issue};
+    pub use crate::config::Config;
+    pub use crate::editor::editor_command;
+    pub use crate::error::{Error, Result};
+    pub use crate::graph::{Edge, Graph};
+    pub use crate::id::NoteIdScheme;
+    pub use crate::journal::{neighbours, open_entry, JournalConfig, Period};
+    pub use crate::kinds::{inbox, note_kind, parse_age, plan_promote, NoteKind};
+    pub use crate::links::{parse_links, Link, LinkKind};
+    pub use crate::note::parse_date;
+    pub use crate::query::{query, QueryResult};
+    pub use crate::rename::{plan_move, write_changes, FileChange, Move};
+    pub use crate::search::{search, SearchHit};
+    pub use crate::tags::{plan_retag, tag_counts, tag_matches, TagCount};
+    pub use crate::vault::{Backlink, NoteFilter, SortKey, Vault};
+
+    /// Notes deleted with `ztr rm`, kept in `.ztr/trash` until it is emptied.
+    pub mod trash {
+        pub use crate::trash::{empty, list, remove, restore, trash_dir, TrashEntry};
+    }
+
     pub struct NewNote {
         pub template: Option<String>,
         pub title: Option<String>,
         pub content: Option<String>,
         pub tags: Option<Vec<String>>,
+        /// Folgezettel ID to branch the new note from
+        pub parent: Option<String>,
+        /// Kind of the new note, such as `literature`, recorded in its frontmatter
+        pub kind: Option<String>,
+        /// Extra values for the template, such as `author` or `source`
+        pub vars: BTreeMap<String, serde_json::Value>,
     }
 
     impl NewNote {
@@ -76,19 +245,56 @@ pub mod ztr {
                 title,
                 content,
                 tags,
+                parent: None,
+                kind: None,
+                vars: BTreeMap::new(),
             }
         }
     }
 
-    #[derive(Serialize)]
+    #[derive(Serialize, Clone, Debug)]
     pub struct Note {
+        /// Flattened first so a var cannot shadow the fields below
+        #[serde(flatten)]
+        pub vars: BTreeMap<String, serde_json::Value>,
         pub template: String,
         pub filename: String,
+        /// Filename without the `.md` extension
+        pub id: String,
+        pub created: Option<chrono::DateTime<chrono::Local>>,
+        pub modified: Option<chrono::DateTime<chrono::Local>>,
         pub title: String,
         pub content: String,
         pub tags: Vec<String>,
+        /// Where the note was read from
+        #[serde(skip)]
+        pub path: Option<path::PathBuf>,
+        /// Lines the frontmatter takes up before `content` in the file
+        #[serde(skip)]
+        pub content_offset: usize,
+        /// Links in `content`, with lines counted from the start of the file
+        #[serde(skip)]
+        pub links: Vec<Link>,
+        /// Frontmatter the note was parsed from, kept to write it back unchanged
+        #[serde(skip)]
+        pub frontmatter: serde_yaml::Mapping,
+    }
+
+    /// Fields of [`Note`] that templates can always use.
+    pub const NOTE_FIELDS: &[&str] = &[
+        "template", "filename", "id", "created", "modified", "title", "content", "tags",
+    ];
+
+    /// A variable a template uses that the note does not supply.
+    #[derive(Debug, PartialEq)]
+    pub struct TemplateVar {
+        pub name: String,
+        /// Declared in the template's front-matter
+        pub default: Option<serde_json::Value>,
     }
 
+    #[derive(Deserialize, Clone)]
+    #[serde(default)]
     pub struct DefaultNote {
         pub template: String,
         pub title: String,
@@ -115,19 +321,96 @@ pub mod ztr {
         }
     }
 
-    pub fn create(zk_root: &path::Path) -> path::PathBuf {
-        open_create(
-            zk_root,
-            &(generate),
-            &(NewNote {
-                template: None,
-                title: None,
-                content: None,
-                tags: None,
-            }),
-            &DefaultNote::new(),
+    /// Resolves the zettelkasten root from the `--root` flag, the `ZTR_ROOT`
+    /// environment variable or the `root` key of the global config file, in that order.
+    pub fn resolve_root(root: Option<path::PathBuf>) -> Result<path::PathBuf> {
+        let config_root = config::load(&global_layers())?.root;
+        let env_root = std::env::var_os("ZTR_ROOT")
+            .filter(|root| !root.is_empty())
+            .map(path::PathBuf::from);
+
+        config::resolve_root(root, env_root, config_root)
+    }
+
+    /// Loads the global config merged with the per-vault `.ztr/config.toml` of `zk_root`.
+    pub fn load_config(zk_root: &path::Path) -> Result<Config> {
+        let mut layers = global_layers();
+        layers.push(config::vault_config_path(zk_root));
+
+        config::load(&layers)
+    }
+
+    fn global_layers() -> Vec<path::PathBuf> {
+        config::global_config_path().into_iter().collect()
+    }
+
+    /// Variables the note's template uses that `note` does not supply. [`create`] fills the ones
+    /// with a default and fails on the rest, so callers can ask the user for them first.
+    pub fn missing_vars(
+        zk_root: &path::Path,
+        note: &NewNote,
+        config: &Config,
+    ) -> Result<Vec<TemplateVar>> {
+        let default = kind_defaults(note, config)?;
+        let template = note.template.as_ref().unwrap_or(&default.template);
+        let templates = templates::load(zk_root)?;
+        let (hb, name, defaults) = note_registry(template, &templates)?;
+
+        Ok(unsupplied_vars(&hb, &name, &defaults, &note.vars))
+    }
+
+    /// Opens `note_path` in the configured editor, waits for it and reads the note back.
+    /// The `{line}` placeholder of the editor command is the first line after the frontmatter.
+    pub fn edit(note_path: &path::Path, config: &Config) -> Result<Note> {
+        let raw = fs::read_to_string(note_path)?;
+        editor::edit(
+            &editor_command(config.editor.as_deref()),
+            note_path,
+            note::body_line(&raw),
         )
-        .unwrap()
+    }
+
+    pub fn create(zk_root: &path::Path, note: &NewNote, config: &Config) -> Result<path::PathBuf> {
+        let title = note.title.as_ref().unwrap_or(&config.note.title);
+        let name_generator = config
+            .id_scheme
+            .generator(zk_root, title, note.parent.as_deref())?;
+
+        let default = kind_defaults(note, config)?;
+        let dir = match note
+            .kind
+            .as_deref()
+            .map(|kind| config.kind(kind))
+            .transpose()?
+        {
+            Some(NoteKind { dir: Some(dir), .. }) => dir,
+            _ => return open_create(zk_root, &name_generator, note, &default),
+        };
+        fs::create_dir_all(zk_root.join(&dir))?;
+        let in_dir = || format!("{}/{}", dir.to_string_lossy(), name_generator());
+        open_create(zk_root, &in_dir, note, &default)
+    }
+
+    /// The configured defaults for new notes, with the template of the note's kind
+    /// and without the tags naming a kind.
+    fn kind_defaults(note: &NewNote, config: &Config) -> Result<DefaultNote> {
+        let Some(kind) = &note.kind else {
+            return Ok(config.note.clone());
+        };
+        Ok(DefaultNote {
+            template: config
+                .kind(kind)?
+                .template
+                .unwrap_or(config.note.template.clone()),
+            tags: config
+                .note
+                .tags
+                .iter()
+                .filter(|tag| !config.is_kind(tag))
+                .cloned()
+                .collect(),
+            ..config.note.clone()
+        })
     }
 }
 
@@ -330,4 +613,245 @@ mod tests {
         let output = std::fs::read_to_string(result.unwrap()).unwrap();
         assert_eq!(output, expected);
     }
+
+    #[test]
+    fn test_create_should_populate_id_and_created_in_template() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+
+        let zk_root = temp_dir.path().to_path_buf();
+        let name_generator = || String::from("test");
+        let note = NewNote::new(Some(String::from("{{id}} {{created}}")), None, None, None);
+        let default = create_note_defaults();
+
+        let result = open_create(&zk_root, &name_generator, &note, &default);
+
+        let output = std::fs::read_to_string(result.unwrap()).unwrap();
+        let (id, created) = output.split_once(' ').unwrap();
+        assert_eq!(id, "test");
+        assert!(chrono::DateTime::parse_from_rfc3339(created).is_ok());
+    }
+
+    #[test]
+    fn test_create_should_populate_vars_in_template() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+
+        let zk_root = temp_dir.path().to_path_buf();
+        let name_generator = || String::from("test");
+        let note = NewNote {
+            vars: BTreeMap::from([
+                (String::from("author"), serde_json::json!("Luhmann")),
+                (String::from("title"), serde_json::json!("shadowed")),
+            ]),
+            ..NewNote::new(
+                Some(String::from("{{author}}: {{title}}")),
+                Some(String::from("test-title")),
+                None,
+                None,
+            )
+        };
+        let default = create_note_defaults();
+
+        let result = open_create(&zk_root, &name_generator, &note, &default);
+
+        let output = std::fs::read_to_string(result.unwrap()).unwrap();
+        assert_eq!(output, "Luhmann: test-title");
+    }
+
+    #[test]
+    fn test_create_should_fail_on_missing_vars() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+
+        let zk_root = temp_dir.path().to_path_buf();
+        let name_generator = || String::from("test");
+        let note = NewNote::new(
+            Some(String::from("{{title}} {{author}} {{year}}")),
+            None,
+            None,
+            None,
+        );
+        let default = create_note_defaults();
+
+        let result = open_create(&zk_root, &name_generator, &note, &default);
+
+        match result.unwrap_err() {
+            Error::MissingVariables(missing) => assert_eq!(missing, vec!["author", "year"]),
+            e => panic!("unexpected error {}", e),
+        }
+        assert!(!temp_dir.child("test.md").exists());
+    }
+
+    #[test]
+    fn test_create_should_fill_missing_vars_with_defaults() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        temp_dir
+            .child(".ztr/templates/literature.hbs")
+            .write_str("+++\n[vars]\nauthor = \"Unknown\"\n+++\n{{author}} {{year}}")
+            .unwrap();
+
+        let zk_root = temp_dir.path().to_path_buf();
+        let name_generator = || String::from("test");
+        let note = NewNote {
+            vars: BTreeMap::from([(String::from("year"), serde_json::json!(1981))]),
+            ..NewNote::new(Some(String::from("literature")), None, None, None)
+        };
+        let default = create_note_defaults();
+
+        let result = open_create(&zk_root, &name_generator, &note, &default);
+
+        let output = std::fs::read_to_string(result.unwrap()).unwrap();
+        assert_eq!(output, "Unknown 1981");
+    }
+
+    #[test]
+    fn test_create_should_not_overwrite_existing_note() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        temp_dir.child("test.md").write_str("existing").unwrap();
+
+        let zk_root = temp_dir.path().to_path_buf();
+        let name_generator = || String::from("test");
+        let note = NewNote::new(None, None, None, None);
+        let default = create_note_defaults();
+
+        let result = open_create(&zk_root, &name_generator, &note, &default);
+
+        assert!(matches!(
+            result.unwrap_err(),
+            Error::Collision {
+                attempts: MAX_CREATE_ATTEMPTS,
+                ..
+            }
+        ));
+        temp_dir.child("test.md").assert("existing");
+    }
+
+    #[test]
+    fn test_create_should_fail_on_invalid_template() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+
+        let zk_root = temp_dir.path().to_path_buf();
+        let name_generator = || String::from("test");
+        let note = NewNote::new(Some(String::from("{{#if}}")), None, None, None);
+        let default = create_note_defaults();
+
+        let result = open_create(&zk_root, &name_generator, &note, &default);
+
+        assert!(matches!(result.unwrap_err(), Error::TemplateParse(_)));
+        assert!(!temp_dir.child("test.md").exists());
+    }
+
+    #[test]
+    fn test_create_should_render_named_template_with_partial() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        temp_dir
+            .child(".ztr/templates/frontmatter.hbs")
+            .write_str("---\ntitle: {{title}}\n---\n")
+            .unwrap();
+        temp_dir
+            .child(".ztr/templates/literature.hbs")
+            .write_str("{{> frontmatter}}{{content}}")
+            .unwrap();
+
+        let zk_root = temp_dir.path().to_path_buf();
+        let name_generator = || String::from("test");
+        let note = NewNote::new(
+            Some(String::from("literature")),
+            Some(String::from("test-title")),
+            Some(String::from("test-content")),
+            None,
+        );
+        let default = create_note_defaults();
+
+        let expected = String::from("---\ntitle: test-title\n---\ntest-content");
+        let result = open_create(&zk_root, &name_generator, &note, &default);
+
+        let output = std::fs::read_to_string(result.unwrap()).unwrap();
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn test_create_should_use_partials_in_inline_template() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        temp_dir
+            .child(".ztr/templates/footer.hbs")
+            .write_str("-- {{title}}")
+            .unwrap();
+
+        let zk_root = temp_dir.path().to_path_buf();
+        let name_generator = || String::from("test");
+        let note = NewNote::new(
+            Some(String::from("{{content}} {{> footer}}")),
+            Some(String::from("test-title")),
+            Some(String::from("test-content")),
+            None,
+        );
+        let default = create_note_defaults();
+
+        let result = open_create(&zk_root, &name_generator, &note, &default);
+
+        let output = std::fs::read_to_string(result.unwrap()).unwrap();
+        assert_eq!(output, "test-content -- test-title");
+    }
+
+    #[test]
+    fn test_create_should_retry_with_next_name_on_collision() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        temp_dir.child("test.md").write_str("existing").unwrap();
+
+        let zk_root = temp_dir.path().to_path_buf();
+        let name_generator = ztr::NoteIdScheme::Slug
+            .generator(&zk_root, "test", None)
+            .unwrap();
+        let note = NewNote::new(None, None, None, None);
+        let default = create_note_defaults();
+
+        let result = open_create(&zk_root, &name_generator, &note, &default);
+
+        assert_eq!(result.unwrap(), temp_dir.path().join("test-2.md"));
+        temp_dir.child("test.md").assert("existing");
+    }
+
+    #[test]
+    fn test_create_should_not_reuse_id_of_note_in_other_directory() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        temp_dir.child("lit/test.md").write_str("existing").unwrap();
+
+        let zk_root = temp_dir.path().to_path_buf();
+        let name_generator = ztr::NoteIdScheme::Slug
+            .generator(&zk_root, "test", None)
+            .unwrap();
+        let note = NewNote::new(None, None, None, None);
+        let default = create_note_defaults();
+
+        let result = open_create(&zk_root, &name_generator, &note, &default);
+
+        assert_eq!(result.unwrap(), temp_dir.path().join("test-2.md"));
+    }
+
+    #[test]
+    fn test_create_should_use_template_and_dir_of_kind() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+
+        let kind = ztr::NoteKind {
+            template: Some(String::from(
+                "---\nsource: web\n---\n# {{title}} ({{kind}}) {{#each tags}}#[[{{this}}]]{{/each}}",
+            )),
+            dir: Some(path::PathBuf::from("lit")),
+        };
+        let config = ztr::Config {
+            id_scheme: ztr::NoteIdScheme::Slug,
+            kinds: BTreeMap::from([(String::from("literature"), kind)]),
+            ..ztr::Config::default()
+        };
+        let note = NewNote {
+            kind: Some(String::from("literature")),
+            ..NewNote::new(None, Some(String::from("test")), None, None)
+        };
+
+        let result = ztr::create(temp_dir.path(), &note, &config);
+
+        assert_eq!(result.unwrap(), temp_dir.path().join("lit/test.md"));
+        temp_dir
+            .child("lit/test.md")
+            .assert("---\nkind: literature\nsource: web\n---\n# test (literature) ");
+    }
 }
@@ -0,0 +1,266 @@
+use serde_derive::{Deserialize, Serialize};
+use std::ops::Range;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkKind {
+    /// `[[target]]` or `[[target|label]]`
+    Wiki,
+    /// `[label](target.md)`
+    Markdown,
+}
+
+/// A link from a note's body to another note, as written.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Link {
+    pub kind: LinkKind,
+    /// Target without any `#heading` anchor
+    pub target: String,
+    pub label: Option<String>,
+    /// Line in the body, counting from 1
+    pub line: usize,
+    /// Column of the opening bracket, counting from 1
+    pub column: usize,
+    /// Bytes of the line holding the target as written, to rewrite it in place
+    #[serde(skip)]
+    pub span: Range<usize>,
+}
+
+/// Every link in `content`, skipping `#[[tag]]`s, fenced code blocks and inline code.
+pub fn parse_links(content: &str) -> Vec<Link> {
+    let mut links = vec![];
+    let mut fence: Option<&str> = None;
+    for (i, line) in content.lines().enumerate() {
+        let trimmed = line.trim_start();
+        match fence {
+            Some(open) => {
+                if trimmed.starts_with(open) {
+                    fence = None;
+                }
+                continue;
+            }
+            None => {
+                if let Some(open) = ["```", "~~~"].into_iter().find(|f| trimmed.starts_with(f)) {
+                    fence = Some(open);
+                    continue;
+                }
+            }
+        }
+        parse_line(line, i + 1, &mut links);
+    }
+    links
+}
+
+fn parse_line(line: &str, line_number: usize, links: &mut Vec<Link>) {
+    let mut in_code = false;
+    let mut i = 0;
+    while i < line.len() {
+        let rest = &line[i..];
+        if rest.starts_with('`') {
+            in_code = !in_code;
+        } else if !in_code && rest.starts_with("[[") {
+            if let Some(len) = wiki_link(line, i, line_number, links) {
+                i += len;
+                continue;
+            }
+        } else if !in_code && rest.starts_with('[') {
+            if let Some(len) = markdown_link(line, i, line_number, links) {
+                i += len;
+                continue;
+            }
+        }
+        i += rest.chars().next().map_or(1, char::len_utf8);
+    }
+}
+
+/// Parses a wiki link starting at `start` and returns its length.
+fn wiki_link(line: &str, start: usize, line_number: usize, links: &mut Vec<Link>) -> Option<usize> {
+    let inner_start = start + 2;
+    let len = line[inner_start..].find("]]")?;
+    let inner = &line[inner_start..inner_start + len];
+    let is_tag = line[..start].ends_with('#');
+    if is_tag || inner.is_empty() || inner.contains('[') {
+        return Some(len + 4);
+    }
+
+    let (target, label) = match inner.split_once('|') {
+        Some((target, label)) => (target, Some(label.trim().to_string())),
+        None => (inner, None),
+    };
+    let target = strip_anchor(target.trim());
+    if !target.is_empty() {
+        links.push(Link {
+            kind: LinkKind::Wiki,
+            target: target.to_string(),
+            label,
+            line: line_number,
+            column: line[..start].chars().count() + 1,
+            span: span(line, target),
+        });
+    }
+    Some(len + 4)
+}
+
+/// Parses a `[label](target.md)` link starting at `start` and returns its length.
+/// Links to anything but a local `.md` file are ignored, and so is a `[` whose label holds
+/// another bracket, which leaves the link to the `[` closest to its `](`.
+fn markdown_link(
+    line: &str,
+    start: usize,
+    line_number: usize,
+    links: &mut Vec<Link>,
+) -> Option<usize> {
+    let label_len = line[start + 1..].find("](")?;
+    let label = &line[start + 1..start + 1 + label_len];
+    if label.contains(['[', ']']) {
+        return None;
+    }
+    let target_start = start + 1 + label_len + 2;
+    let target_len = line[target_start..].find(')')?;
+    let raw_target = line[target_start..target_start + target_len].trim();
+    let len = target_start + target_len + 1 - start;
+
+    let target = strip_anchor(raw_target.split_whitespace().next().unwrap_or(""));
+    let is_image = line[..start].ends_with('!');
+    if is_image || target.contains("://") || !target.ends_with(".md") {
+        return Some(len);
+    }
+
+    links.push(Link {
+        kind: LinkKind::Markdown,
+        target: target.replace("%20", " "),
+        label: Some(label.to_string()).filter(|label| !label.is_empty()),
+        line: line_number,
+        column: line[..start].chars().count() + 1,
+        span: span(line, target),
+    });
+    Some(len)
+}
+
+/// `line` trimmed, or a window of it around `column` when it is long.
+pub fn snippet(line: &str, column: usize) -> String {
+    const WIDTH: usize = 80;
+    let chars: Vec<char> = line.trim_end().chars().collect();
+    let indent = chars.iter().take_while(|c| c.is_whitespace()).count();
+    if chars.len() - indent <= WIDTH {
+        return chars[indent..].iter().collect();
+    }
+
+    let start = column
+        .saturating_sub(1 + WIDTH / 4)
+        .max(indent)
+        .min(chars.len() - WIDTH);
+    let end = start + WIDTH;
+    let mut snippet: String = chars[start..end].iter().collect();
+    if start > indent {
+        snippet.insert(0, '…');
+    }
+    if end < chars.len() {
+        snippet.push('…');
+    }
+    snippet
+}
+
+/// Byte range of `part`, a slice of `line`.
+fn span(line: &str, part: &str) -> Range<usize> {
+    let start = part.as_ptr() as usize - line.as_ptr() as usize;
+    start..start + part.len()
+}
+
+fn strip_anchor(target: &str) -> &str {
+    target.split('#').next().unwrap_or(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn targets(content: &str) -> Vec<String> {
+        parse_links(content)
+            .into_iter()
+            .map(|link| link.target)
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_links_should_read_all_link_styles() {
+        let links = parse_links("See [[abc]] and [[def|the def note]].\n[Ghi](sub/ghi.md#part)");
+
+        assert_eq!(
+            links,
+            vec![
+                Link {
+                    kind: LinkKind::Wiki,
+                    target: String::from("abc"),
+                    label: None,
+                    line: 1,
+                    column: 5,
+                    span: 6..9,
+                },
+                Link {
+                    kind: LinkKind::Wiki,
+                    target: String::from("def"),
+                    label: Some(String::from("the def note")),
+                    line: 1,
+                    column: 17,
+                    span: 18..21,
+                },
+                Link {
+                    kind: LinkKind::Markdown,
+                    target: String::from("sub/ghi.md"),
+                    label: Some(String::from("Ghi")),
+                    line: 2,
+                    column: 1,
+                    span: 6..16,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_links_should_start_markdown_label_at_last_bracket() {
+        let links = parse_links("see [x] and [b](b.md), [a [c](c.md)");
+
+        let found: Vec<(Option<String>, usize)> = links
+            .into_iter()
+            .map(|link| (link.label, link.column))
+            .collect();
+        assert_eq!(
+            found,
+            vec![(Some(String::from("b")), 13), (Some(String::from("c")), 27)]
+        );
+    }
+
+    #[test]
+    fn test_parse_links_should_skip_tags() {
+        assert_eq!(targets("#[[fleeting]] #[[idea]] [[real]]"), vec!["real"]);
+    }
+
+    #[test]
+    fn test_parse_links_should_skip_code_urls_and_images() {
+        let content = "`[[inline]]`\n```\n[[fenced]]\n```\n[web](https://x.org/a.md) ![img](a.md)";
+
+        assert!(targets(content).is_empty());
+    }
+
+    #[test]
+    fn test_snippet_should_trim_short_lines() {
+        assert_eq!(snippet("  see [[abc]] ", 7), "see [[abc]]");
+    }
+
+    #[test]
+    fn test_snippet_should_window_long_lines_around_column() {
+        let line = format!("{}[[abc]]{}", "a".repeat(100), "b".repeat(100));
+
+        let snippet = snippet(&line, 101);
+
+        assert_eq!(snippet.chars().count(), 82);
+        assert!(snippet.starts_with('…') && snippet.ends_with('…'));
+        assert!(snippet.contains("[[abc]]"));
+    }
+
+    #[test]
+    fn test_parse_links_should_strip_heading_anchors() {
+        assert_eq!(targets("[[abc#Section|label]]"), vec!["abc"]);
+    }
+}
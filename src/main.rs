@@ -1,9 +1,17 @@
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::io::{BufRead, IsTerminal, Read, Write};
+use std::{fs, io, iter, path};
 use ztr::ztr;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
+    /// Zettelkasten root directory, overrides ZTR_ROOT and the config file
+    #[arg(long, global = true)]
+    root: Option<std::path::PathBuf>,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -11,19 +19,1066 @@ struct Cli {
 #[derive(Subcommand)]
 enum Commands {
     /// Create a new Zettle
-    Create {},
+    Create {
+        /// Title of the note
+        #[arg(long)]
+        title: Option<String>,
+
+        /// Body of the note, `-` reads it from stdin
+        #[arg(long)]
+        content: Option<String>,
+
+        /// Tag to add to the note, can be repeated
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+
+        /// Name of a template in .ztr/templates, an inline handlebars string or `@path` to a file
+        #[arg(long)]
+        template: Option<String>,
+
+        /// Folgezettel ID to branch from when using the folgezettel id scheme
+        #[arg(long)]
+        parent: Option<String>,
+
+        /// Template variable as `key=value`, can be repeated
+        #[arg(long = "var", value_name = "KEY=VALUE", value_parser = parse_var)]
+        vars: Vec<(String, String)>,
+
+        /// JSON object of template variables, `--var` takes precedence
+        #[arg(long)]
+        vars_file: Option<path::PathBuf>,
+
+        /// Kind of the note, such as `literature`, using the kind's template and directory
+        #[arg(long)]
+        kind: Option<String>,
+
+        /// Open the new note in the editor
+        #[arg(long)]
+        edit: bool,
+    },
+    /// Open a note in the editor
+    Edit {
+        /// ID, filename or title of the note
+        #[arg(required_unless_present = "saved", conflicts_with = "saved")]
+        query: Option<String>,
+
+        /// Open the notes found by this saved search one after the other
+        #[arg(long)]
+        saved: Option<String>,
+    },
+    /// List the notes of the zettelkasten
+    List {
+        /// Only notes with this tag, can be repeated to require several
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+
+        /// Only notes created on or after this date
+        #[arg(long, value_parser = parse_since)]
+        since: Option<chrono::DateTime<chrono::Local>>,
+
+        /// Only notes created on or before this date
+        #[arg(long, value_parser = parse_until)]
+        until: Option<chrono::DateTime<chrono::Local>>,
+
+        /// Order of the notes, by ID when not given
+        #[arg(long, value_enum)]
+        sort: Option<SortArg>,
+
+        /// Print at most this many notes
+        #[arg(long)]
+        limit: Option<usize>,
+
+        /// Only notes found by this saved search
+        #[arg(long)]
+        saved: Option<String>,
+
+        #[arg(long, value_enum, default_value_t = ListFormat::Table)]
+        format: ListFormat,
+    },
+    /// Run a saved search from the `[saved]` config table, or list them without a name
+    Saved {
+        /// Name of the saved search
+        name: Option<String>,
+
+        #[arg(long, value_enum, default_value_t = ListFormat::Table)]
+        format: ListFormat,
+    },
+    /// List the notes linking to a note
+    Backlinks {
+        /// ID, filename or title of the note
+        query: String,
+
+        #[arg(long, value_enum, default_value_t = BacklinksFormat::Text)]
+        format: BacklinksFormat,
+    },
+    /// Search titles, tags and bodies, best matches first
+    Search {
+        /// Words to find; `"a phrase"`, `prefix*` and `-excluded` are supported. Excluded words
+        /// go after `--`, as in `ztr search rust -- -pans`
+        #[arg(required = true, num_args = 1..)]
+        query: Vec<String>,
+
+        /// Print at most this many notes
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+    /// Find notes with a query such as `tag:project AND created>=2026-01-01 ORDER BY title`
+    Query {
+        /// `[SELECT field, ...] [WHERE] conditions [ORDER BY field [ASC|DESC], ...] [LIMIT n]`
+        query: String,
+
+        #[arg(long, value_enum, default_value_t = ListFormat::Table)]
+        format: ListFormat,
+    },
+    /// Rename a note and rewrite every link to it
+    Mv {
+        /// ID, filename or title of the note
+        old: String,
+
+        /// New filename, with or without `.md`, below the vault root when it contains a `/`
+        new: String,
+
+        /// Also change the note's title, and links written as the old title
+        #[arg(long)]
+        title: Option<String>,
+
+        /// Print the changes as a diff without writing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Turn a note into another kind, such as a fleeting note into a permanent one
+    Promote {
+        /// ID, filename or title of the note
+        query: String,
+
+        /// The new kind
+        #[arg(long)]
+        to: String,
+
+        /// Print the changes as a diff without writing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// List fleeting notes waiting to be promoted or deleted, oldest first
+    Inbox {
+        /// Only notes older than this, such as `7d` or `2w`, instead of `inbox_age`
+        #[arg(long, value_parser = parse_age)]
+        older_than: Option<chrono::Duration>,
+
+        #[arg(long, value_enum, default_value_t = ListFormat::Table)]
+        format: ListFormat,
+    },
+    /// Open the journal entry of a day, creating it when missing
+    Daily(JournalArgs),
+    /// Open the journal entry of a week, creating it when missing
+    Weekly(JournalArgs),
+    /// Open the journal entry of a month, creating it when missing
+    Monthly(JournalArgs),
+    /// Move a note to the trash
+    Rm {
+        /// ID, filename or title of the note
+        query: String,
+
+        /// Delete the note even though other notes link to it
+        #[arg(long)]
+        force: bool,
+    },
+    /// List, restore or delete the notes in the trash
+    Trash {
+        #[command(subcommand)]
+        command: TrashCommands,
+    },
+    /// List tags with how many notes carry them, or rename and merge tags
+    Tags {
+        #[command(subcommand)]
+        command: Option<TagsCommands>,
+
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+    /// Report broken links, orphans, untagged notes, duplicate titles and IDs, and invalid
+    /// frontmatter
+    Check {
+        /// Also exit with 1 on warnings: orphans and untagged notes
+        #[arg(long)]
+        strict: bool,
+
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+}
+
+#[derive(Args)]
+struct JournalArgs {
+    /// A date within the entry's period, today when not given
+    #[arg(long, value_parser = parse_since)]
+    date: Option<chrono::DateTime<chrono::Local>>,
+
+    /// Open the nearest existing entry before the date instead
+    #[arg(long, conflicts_with = "next")]
+    prev: bool,
+
+    /// Open the nearest existing entry after the date instead
+    #[arg(long)]
+    next: bool,
+
+    /// Print the entry's path without opening the editor
+    #[arg(long)]
+    no_edit: bool,
+}
+
+#[derive(Subcommand)]
+enum TrashCommands {
+    /// List the notes in the trash, oldest first
+    List,
+    /// Move a note back to where it was deleted from
+    Restore {
+        /// ID of the note, or its file name in the trash
+        query: String,
+    },
+    /// Delete the notes in the trash for good
+    Empty,
+}
+
+#[derive(Subcommand)]
+enum TagsCommands {
+    /// Rename a tag and its descendants in every note
+    Rename {
+        old: String,
+        new: String,
+
+        /// Print the changes as a diff without writing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Replace several tags with one
+    Merge {
+        #[arg(required = true, num_args = 1..)]
+        tags: Vec<String>,
+
+        /// The tag that replaces them
+        #[arg(long)]
+        into: String,
+
+        /// Print the changes as a diff without writing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+enum SortArg {
+    Title,
+    Created,
+    Modified,
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+enum ListFormat {
+    Table,
+    Json,
+    Ndjson,
+    Tsv,
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+enum BacklinksFormat {
+    Text,
+    Json,
+    /// `path:line:col: text`, for vim's quickfix list
+    Quickfix,
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+enum OutputFormat {
+    Text,
+    Json,
+}
+
+/// A row of `ztr query` output, serialized as an object with the selected fields in order.
+struct QueryRow<'a>(&'a [(&'a str, Value)]);
+
+impl serde::Serialize for QueryRow<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeMap;
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (field, value) in self.0 {
+            map.serialize_entry(field, value)?;
+        }
+        map.end()
+    }
+}
+
+#[derive(serde_derive::Serialize)]
+struct SearchEntry<'a> {
+    id: &'a str,
+    title: &'a str,
+    path: Option<&'a path::Path>,
+    score: f64,
+    snippet: &'a str,
+    /// Byte ranges of `snippet` that matched
+    highlights: Vec<(usize, usize)>,
+}
+
+#[derive(serde_derive::Serialize)]
+struct BacklinkEntry<'a> {
+    id: &'a str,
+    title: &'a str,
+    path: Option<&'a path::Path>,
+    line: usize,
+    column: usize,
+    snippet: &'a str,
+}
+
+#[derive(serde_derive::Serialize)]
+struct ListEntry<'a> {
+    id: &'a str,
+    title: &'a str,
+    tags: &'a [String],
+    created: Option<String>,
+    modified: Option<String>,
+    path: Option<&'a path::Path>,
 }
 
 fn main() {
     let cli = Cli::parse();
 
     match cli.command {
-        Some(Commands::Create {}) => {
-            let name = ztr::create(&std::path::PathBuf::from(r#"/Users/meysi/notebox"#));
+        Some(Commands::Create {
+            title,
+            content,
+            tags,
+            template,
+            parent,
+            vars,
+            vars_file,
+            kind,
+            edit,
+        }) => {
+            let root = or_exit(ztr::resolve_root(cli.root));
+            let config = or_exit(ztr::load_config(&root));
+            let mut note_vars = match vars_file {
+                Some(vars_file) => or_exit(read_vars_file(&vars_file)),
+                None => BTreeMap::new(),
+            };
+            note_vars.extend(vars.into_iter().map(|(k, v)| (k, Value::String(v))));
+            let mut note = ztr::NewNote {
+                parent,
+                kind,
+                vars: note_vars,
+                ..ztr::NewNote::new(
+                    template.map(|t| or_exit(read_template(&t))),
+                    title,
+                    content.map(|c| or_exit(read_content(&c))),
+                    (!tags.is_empty()).then_some(tags),
+                )
+            };
+            if io::stdin().is_terminal() {
+                for var in or_exit(ztr::missing_vars(&root, &note, &config)) {
+                    let value = or_exit(prompt_var(&var));
+                    note.vars.insert(var.name, value);
+                }
+            }
+            let name = or_exit(ztr::create(&root, &note, &config));
+            if edit {
+                or_exit(ztr::edit(&name, &config));
+            }
             print!("{}", name.to_string_lossy())
         }
+        Some(Commands::Edit { query, saved }) => {
+            let root = or_exit(ztr::resolve_root(cli.root));
+            let config = or_exit(ztr::load_config(&root));
+            // Saved queries may look at bodies, other commands only resolve notes.
+            let vault = or_exit(match saved {
+                Some(_) => ztr::Vault::load(&root),
+                None => ztr::Vault::load_without_bodies(&root),
+            });
+            let note_paths = match (query, saved) {
+                (Some(query), _) => vec![or_exit(vault.resolve_path(&query))],
+                (None, Some(saved)) => {
+                    let result = or_exit(ztr::query(&vault, or_exit(config.saved_query(&saved))));
+                    result
+                        .notes
+                        .iter()
+                        .filter_map(|note| note.path.clone())
+                        .collect()
+                }
+                (None, None) => vec![],
+            };
+            for note_path in note_paths {
+                or_exit(ztr::edit(&note_path, &config));
+            }
+        }
+        Some(Commands::List {
+            tags,
+            since,
+            until,
+            sort,
+            limit,
+            saved,
+            format,
+        }) => {
+            let root = or_exit(ztr::resolve_root(cli.root));
+            let vault = or_exit(match saved {
+                Some(_) => ztr::Vault::load(&root),
+                None => ztr::Vault::load_without_bodies(&root),
+            });
+            for (note_path, e) in &vault.invalid {
+                eprintln!("warning: skipping {}: {}", note_path.display(), e);
+            }
+
+            let ids = saved.map(|saved| {
+                let config = or_exit(ztr::load_config(&root));
+                let result = or_exit(ztr::query(&vault, or_exit(config.saved_query(&saved))));
+                result.notes.iter().map(|note| note.id.clone()).collect()
+            });
+            let filter = ztr::NoteFilter {
+                tags,
+                since,
+                until,
+                ids,
+            };
+            let sort = match sort {
+                None => ztr::SortKey::Id,
+                Some(SortArg::Title) => ztr::SortKey::Title,
+                Some(SortArg::Created) => ztr::SortKey::Created,
+                Some(SortArg::Modified) => ztr::SortKey::Modified,
+            };
+            let notes = vault.list(&filter, sort, limit);
+            or_exit(print_list(&notes, format));
+        }
+        Some(Commands::Saved { name, format }) => {
+            let root = or_exit(ztr::resolve_root(cli.root));
+            let config = or_exit(ztr::load_config(&root));
+            match name {
+                Some(name) => {
+                    let vault = or_exit(ztr::Vault::load(&root));
+                    let result = or_exit(ztr::query(&vault, or_exit(config.saved_query(&name))));
+                    or_exit(print_query(&result, format));
+                }
+                None => {
+                    let rows: Vec<[String; 2]> = config
+                        .saved
+                        .iter()
+                        .map(|(name, query)| [name.clone(), query.clone()])
+                        .collect();
+                    let header = ["NAME", "QUERY"].map(String::from);
+                    or_exit(write_table(&mut io::stdout().lock(), &header, &rows));
+                }
+            }
+        }
+        Some(Commands::Backlinks { query, format }) => {
+            let root = or_exit(ztr::resolve_root(cli.root));
+            let vault = or_exit(ztr::Vault::load(&root));
+            let note = or_exit(vault.resolve(&query));
+            or_exit(print_backlinks(&vault.backlinks(note), format));
+        }
+        Some(Commands::Search {
+            query,
+            limit,
+            format,
+        }) => {
+            let root = or_exit(ztr::resolve_root(cli.root));
+            let vault = or_exit(ztr::Vault::load_without_bodies(&root));
+            let hits = or_exit(ztr::search(&vault, &query.join(" "), limit));
+            or_exit(print_hits(&hits, format));
+        }
+        Some(Commands::Query { query, format }) => {
+            let root = or_exit(ztr::resolve_root(cli.root));
+            let vault = or_exit(ztr::Vault::load(&root));
+            let result = or_exit(ztr::query(&vault, &query));
+            or_exit(print_query(&result, format));
+        }
+        Some(Commands::Mv {
+            old,
+            new,
+            title,
+            dry_run,
+        }) => {
+            let root = or_exit(ztr::resolve_root(cli.root));
+            let vault = or_exit(ztr::Vault::load_without_bodies(&root));
+            let note = or_exit(vault.resolve(&old));
+            let planned = or_exit(ztr::plan_move(&vault, note, &new, title.as_deref()));
+            if dry_run {
+                print!("{}", planned.diff(&root));
+            } else {
+                or_exit(planned.apply());
+                print!("{}", planned.to.to_string_lossy());
+            }
+        }
+        Some(Commands::Promote { query, to, dry_run }) => {
+            let root = or_exit(ztr::resolve_root(cli.root));
+            let config = or_exit(ztr::load_config(&root));
+            let vault = or_exit(ztr::Vault::load_without_bodies(&root));
+            let note = or_exit(vault.resolve(&query));
+            let planned = or_exit(ztr::plan_promote(&vault, note, &to, &config));
+            if dry_run {
+                print!("{}", planned.diff(&root));
+            } else {
+                or_exit(planned.apply());
+                print!("{}", planned.to.to_string_lossy());
+            }
+        }
+        Some(Commands::Inbox { older_than, format }) => {
+            let root = or_exit(ztr::resolve_root(cli.root));
+            let config = or_exit(ztr::load_config(&root));
+            let vault = or_exit(ztr::Vault::load_without_bodies(&root));
+            let notes = or_exit(ztr::inbox(&vault, &config, older_than));
+            or_exit(print_list(&notes, format));
+        }
+        Some(Commands::Daily(args)) => open_journal(cli.root, ztr::Period::Daily, &args),
+        Some(Commands::Weekly(args)) => open_journal(cli.root, ztr::Period::Weekly, &args),
+        Some(Commands::Monthly(args)) => open_journal(cli.root, ztr::Period::Monthly, &args),
+        Some(Commands::Rm { query, force }) => {
+            let root = or_exit(ztr::resolve_root(cli.root));
+            let vault = or_exit(ztr::Vault::load_without_bodies(&root));
+            let note = or_exit(vault.resolve(&query));
+            let entry = or_exit(ztr::trash::remove(&vault, note, force));
+            print!(
+                "{}",
+                ztr::trash::trash_dir(&root)
+                    .join(entry.file)
+                    .to_string_lossy()
+            );
+        }
+        Some(Commands::Trash { command }) => {
+            let root = or_exit(ztr::resolve_root(cli.root));
+            match command {
+                TrashCommands::List => {
+                    let entries = or_exit(ztr::trash::list(&root));
+                    or_exit(print_trash(&entries));
+                }
+                TrashCommands::Restore { query } => {
+                    let restored = or_exit(ztr::trash::restore(&root, &query));
+                    print!("{}", restored.to_string_lossy());
+                }
+                TrashCommands::Empty => {
+                    let count = or_exit(ztr::trash::empty(&root));
+                    eprintln!("deleted {} note(s)", count);
+                }
+            }
+        }
+        Some(Commands::Tags { command, format }) => {
+            let root = or_exit(ztr::resolve_root(cli.root));
+            let vault = or_exit(ztr::Vault::load_without_bodies(&root));
+            let Some(command) = command else {
+                or_exit(print_tags(&ztr::tag_counts(&vault), format));
+                return;
+            };
+            let (from, into, dry_run) = match command {
+                TagsCommands::Rename { old, new, dry_run } => (vec![old], new, dry_run),
+                TagsCommands::Merge {
+                    tags,
+                    into,
+                    dry_run,
+                } => (tags, into, dry_run),
+            };
+            let changes = or_exit(ztr::plan_retag(&vault, &from, &into));
+            if dry_run {
+                for change in &changes {
+                    let name = change.path.strip_prefix(&root).unwrap_or(&change.path);
+                    let name = name.display().to_string();
+                    print!("{}", change.diff(&name, &name));
+                }
+            } else {
+                or_exit(ztr::write_changes(&changes));
+                eprintln!("retagged {} note(s)", changes.len());
+            }
+        }
+        Some(Commands::Check { strict, format }) => {
+            let root = or_exit(ztr::resolve_root(cli.root));
+            let vault = or_exit(ztr::Vault::load_without_bodies(&root));
+            let issues = ztr::check(&vault);
+            or_exit(print_issues(&issues, format));
+            let warnings = issues.iter().filter(|issue| issue.is_warning()).count();
+            let errors = issues.len() - warnings;
+            if !issues.is_empty() {
+                eprintln!("{} error(s), {} warning(s) found", errors, warnings);
+            }
+            if errors > 0 || (strict && warnings > 0) {
+                std::process::exit(1);
+            }
+        }
         None => {
             print!("No subcommand was used");
         }
     }
 }
+
+/// Opens the journal entry of `period` that `args` picks, creating it when missing.
+fn open_journal(root: Option<path::PathBuf>, period: ztr::Period, args: &JournalArgs) {
+    let root = or_exit(ztr::resolve_root(root));
+    let config = or_exit(ztr::load_config(&root));
+    let vault = or_exit(ztr::Vault::load_without_bodies(&root));
+    let date = args.date.unwrap_or_else(chrono::Local::now).date_naive();
+
+    let entry = match (args.prev, args.next) {
+        (false, false) => or_exit(ztr::open_entry(&vault, period, date, &config)),
+        (prev, _) => {
+            let (before, after) = ztr::neighbours(&vault, period, date);
+            let neighbour = if prev { before } else { after };
+            or_exit(neighbour.and_then(|note| note.path.clone()).ok_or_else(|| {
+                ztr::Error::NotFound(format!(
+                    "no {} entry {} {}",
+                    period.name(),
+                    if prev { "before" } else { "after" },
+                    period.id(date)
+                ))
+            }))
+        }
+    };
+    if !args.no_edit {
+        or_exit(ztr::edit(&entry, &config));
+    }
+    print!("{}", entry.to_string_lossy());
+}
+
+fn print_list(notes: &[&ztr::Note], format: ListFormat) -> ztr::Result<()> {
+    let entries: Vec<ListEntry> = notes
+        .iter()
+        .map(|note| ListEntry {
+            id: &note.id,
+            title: &note.title,
+            tags: &note.tags,
+            created: note.created.map(rfc3339),
+            modified: note.modified.map(rfc3339),
+            path: note.path.as_deref(),
+        })
+        .collect();
+
+    let mut out = io::stdout().lock();
+    match format {
+        ListFormat::Json => {
+            serde_json::to_writer_pretty(&mut out, &entries).map_err(io::Error::from)?;
+            writeln!(out)?;
+        }
+        ListFormat::Ndjson => {
+            for entry in &entries {
+                serde_json::to_writer(&mut out, entry).map_err(io::Error::from)?;
+                writeln!(out)?;
+            }
+        }
+        ListFormat::Tsv => {
+            for entry in &entries {
+                writeln!(
+                    out,
+                    "{}\t{}\t{}\t{}",
+                    entry.id,
+                    entry.title.replace('\t', " "),
+                    entry.tags.join(","),
+                    entry.modified.as_deref().unwrap_or("")
+                )?;
+            }
+        }
+        ListFormat::Table => {
+            let rows: Vec<[String; 4]> = notes
+                .iter()
+                .map(|note| {
+                    [
+                        note.id.clone(),
+                        note.modified
+                            .map(|modified| modified.format("%Y-%m-%d %H:%M").to_string())
+                            .unwrap_or_default(),
+                        note.title.clone(),
+                        note.tags.join(", "),
+                    ]
+                })
+                .collect();
+            let header = ["ID", "MODIFIED", "TITLE", "TAGS"].map(String::from);
+            write_table(&mut out, &header, &rows)?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes `rows` under `header` in columns padded to the widest cell.
+fn write_table<R: AsRef<[String]>>(
+    out: &mut impl Write,
+    header: &[String],
+    rows: &[R],
+) -> ztr::Result<()> {
+    let mut widths: Vec<usize> = header.iter().map(|column| column.chars().count()).collect();
+    for row in rows {
+        for (width, column) in widths.iter_mut().zip(row.as_ref()) {
+            *width = (*width).max(column.chars().count());
+        }
+    }
+    for row in iter::once(header).chain(rows.iter().map(AsRef::as_ref)) {
+        let line = row
+            .iter()
+            .zip(&widths)
+            .map(|(column, width)| format!("{:<width$}", column, width = width))
+            .collect::<Vec<_>>()
+            .join("  ");
+        writeln!(out, "{}", line.trim_end())?;
+    }
+    Ok(())
+}
+
+fn print_query(result: &ztr::QueryResult, format: ListFormat) -> ztr::Result<()> {
+    let rows: Vec<Vec<(&str, Value)>> = result
+        .notes
+        .iter()
+        .map(|note| {
+            result
+                .fields
+                .iter()
+                .map(|field| (field.as_str(), result.value(note, field)))
+                .collect()
+        })
+        .collect();
+    let cell = |value: &Value, separator: &str| match value {
+        Value::Null => String::new(),
+        Value::String(text) => text.clone(),
+        Value::Array(values) => values
+            .iter()
+            .map(|value| match value {
+                Value::String(text) => text.clone(),
+                value => value.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(separator),
+        value => value.to_string(),
+    };
+
+    let mut out = io::stdout().lock();
+    match format {
+        ListFormat::Json => {
+            let objects: Vec<QueryRow> = rows.iter().map(|row| QueryRow(row)).collect();
+            serde_json::to_writer_pretty(&mut out, &objects).map_err(io::Error::from)?;
+            writeln!(out)?;
+        }
+        ListFormat::Ndjson => {
+            for row in &rows {
+                serde_json::to_writer(&mut out, &QueryRow(row)).map_err(io::Error::from)?;
+                writeln!(out)?;
+            }
+        }
+        ListFormat::Tsv => {
+            for row in rows {
+                let cells: Vec<String> = row
+                    .iter()
+                    .map(|(_, value)| cell(value, ",").replace(['\t', '\n'], " "))
+                    .collect();
+                writeln!(out, "{}", cells.join("\t"))?;
+            }
+        }
+        ListFormat::Table => {
+            let header: Vec<String> = result.fields.iter().map(|f| f.to_uppercase()).collect();
+            let rows: Vec<Vec<String>> = rows
+                .iter()
+                .map(|row| {
+                    row.iter()
+                        .map(|(_, value)| cell(value, ", ").replace('\n', " "))
+                        .collect()
+                })
+                .collect();
+            write_table(&mut out, &header, &rows)?;
+        }
+    }
+    Ok(())
+}
+
+fn print_backlinks(backlinks: &[ztr::Backlink], format: BacklinksFormat) -> ztr::Result<()> {
+    let mut out = io::stdout().lock();
+    match format {
+        BacklinksFormat::Json => {
+            let entries: Vec<BacklinkEntry> = backlinks
+                .iter()
+                .map(|backlink| BacklinkEntry {
+                    id: &backlink.source.id,
+                    title: &backlink.source.title,
+                    path: backlink.source.path.as_deref(),
+                    line: backlink.link.line,
+                    column: backlink.link.column,
+                    snippet: &backlink.snippet,
+                })
+                .collect();
+            serde_json::to_writer_pretty(&mut out, &entries).map_err(io::Error::from)?;
+            writeln!(out)?;
+        }
+        BacklinksFormat::Quickfix => {
+            for backlink in backlinks {
+                let source_path = backlink
+                    .source
+                    .path
+                    .as_deref()
+                    .unwrap_or(path::Path::new(""));
+                writeln!(
+                    out,
+                    "{}:{}:{}: {}",
+                    source_path.display(),
+                    backlink.link.line,
+                    backlink.link.column,
+                    backlink.snippet
+                )?;
+            }
+        }
+        BacklinksFormat::Text => {
+            let mut previous: Option<&str> = None;
+            for backlink in backlinks {
+                let source = backlink.source;
+                if previous != Some(source.id.as_str()) {
+                    if previous.is_some() {
+                        writeln!(out)?;
+                    }
+                    let header = format!("{}  {}", source.id, source.title);
+                    writeln!(out, "{}", header.trim_end())?;
+                    previous = Some(&source.id);
+                }
+                writeln!(out, "  {:>4}: {}", backlink.link.line, backlink.snippet)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn print_issues(issues: &[ztr::Issue], format: OutputFormat) -> ztr::Result<()> {
+    let mut out = io::stdout().lock();
+    match format {
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut out, issues).map_err(io::Error::from)?;
+            writeln!(out)?;
+        }
+        OutputFormat::Text => {
+            for issue in issues {
+                let severity = if issue.is_warning() {
+                    "warning"
+                } else {
+                    "error"
+                };
+                writeln!(out, "{}: {}", severity, issue)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn print_hits(hits: &[ztr::SearchHit], format: OutputFormat) -> ztr::Result<()> {
+    let mut out = io::stdout().lock();
+    match format {
+        OutputFormat::Json => {
+            let entries: Vec<SearchEntry> = hits
+                .iter()
+                .map(|hit| SearchEntry {
+                    id: &hit.note.id,
+                    title: &hit.note.title,
+                    path: hit.note.path.as_deref(),
+                    score: hit.score,
+                    snippet: &hit.snippet,
+                    highlights: hit.highlights.iter().map(|h| (h.start, h.end)).collect(),
+                })
+                .collect();
+            serde_json::to_writer_pretty(&mut out, &entries).map_err(io::Error::from)?;
+            writeln!(out)?;
+        }
+        OutputFormat::Text => {
+            let color = io::stdout().is_terminal();
+            for hit in hits {
+                let header = format!("{}  {}", hit.note.id, hit.note.title);
+                writeln!(out, "{}", header.trim_end())?;
+                let mut snippet = String::new();
+                let mut from = 0;
+                for highlight in hit.highlights.iter().filter(|_| color) {
+                    snippet.push_str(&hit.snippet[from..highlight.start]);
+                    snippet.push_str("\x1b[1m");
+                    snippet.push_str(&hit.snippet[highlight.clone()]);
+                    snippet.push_str("\x1b[0m");
+                    from = highlight.end;
+                }
+                snippet.push_str(&hit.snippet[from..]);
+                if !snippet.is_empty() {
+                    writeln!(out, "    {}", snippet)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn print_tags(counts: &[ztr::TagCount], format: OutputFormat) -> ztr::Result<()> {
+    let mut out = io::stdout().lock();
+    match format {
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut out, counts).map_err(io::Error::from)?;
+            writeln!(out)?;
+        }
+        OutputFormat::Text => {
+            let rows: Vec<[String; 3]> = counts
+                .iter()
+                .map(|count| {
+                    [
+                        count.tag.clone(),
+                        count.notes.to_string(),
+                        count.total.to_string(),
+                    ]
+                })
+                .collect();
+            let header = ["TAG", "NOTES", "TOTAL"].map(String::from);
+            write_table(&mut out, &header, &rows)?;
+        }
+    }
+    Ok(())
+}
+
+fn print_trash(entries: &[ztr::trash::TrashEntry]) -> ztr::Result<()> {
+    let mut out = io::stdout().lock();
+    for entry in entries {
+        writeln!(
+            out,
+            "{}\t{}\t{}",
+            entry.trashed.format("%Y-%m-%d %H:%M:%S"),
+            entry.id,
+            entry.path.display()
+        )?;
+    }
+    Ok(())
+}
+
+fn rfc3339(date: chrono::DateTime<chrono::Local>) -> String {
+    date.to_rfc3339_opts(chrono::SecondsFormat::Secs, false)
+}
+
+fn parse_since(arg: &str) -> Result<chrono::DateTime<chrono::Local>, String> {
+    ztr::parse_date(arg).ok_or_else(|| format!("expected YYYY-MM-DD or RFC 3339, got '{}'", arg))
+}
+
+fn parse_age(arg: &str) -> Result<chrono::Duration, String> {
+    ztr::parse_age(arg)
+        .ok_or_else(|| format!("expected an age such as 12h, 7d, 2w or 1y, got '{}'", arg))
+}
+
+/// Like [`parse_since`], but a plain date includes the whole day.
+fn parse_until(arg: &str) -> Result<chrono::DateTime<chrono::Local>, String> {
+    let until = parse_since(arg)?;
+    Ok(
+        match chrono::NaiveDate::parse_from_str(arg.trim(), "%Y-%m-%d") {
+            Ok(_) => until + chrono::Duration::days(1),
+            Err(_) => until + chrono::Duration::seconds(1),
+        },
+    )
+}
+
+fn or_exit<T>(result: ztr::Result<T>) -> T {
+    result.unwrap_or_else(|e| {
+        if matches!(&e, ztr::Error::Io(io_error) if io_error.kind() == io::ErrorKind::BrokenPipe) {
+            std::process::exit(0);
+        }
+        eprintln!("error: {}", e);
+        std::process::exit(exit_code(&e));
+    })
+}
+
+fn exit_code(error: &ztr::Error) -> i32 {
+    match error {
+        ztr::Error::Io(_) | ztr::Error::File { .. } => 3,
+        ztr::Error::Config(_) => 4,
+        ztr::Error::NotFound(_) => 5,
+        ztr::Error::TemplateParse(_) => 6,
+        ztr::Error::TemplateRender(_) => 7,
+        ztr::Error::InvalidId(_) => 8,
+        ztr::Error::Collision { .. } => 9,
+        ztr::Error::MissingVariables(_) => 10,
+        ztr::Error::Frontmatter(_) => 11,
+        ztr::Error::Ambiguous { .. } => 12,
+        ztr::Error::Editor(_) => 13,
+        ztr::Error::HasBacklinks { .. } => 14,
+        ztr::Error::InvalidQuery(_) => 15,
+        ztr::Error::InvalidTag(_) => 16,
+    }
+}
+
+fn read_template(arg: &str) -> ztr::Result<String> {
+    match arg.strip_prefix('@') {
+        Some(template_path) => {
+            fs::read_to_string(template_path).map_err(|source| ztr::Error::File {
+                path: path::PathBuf::from(template_path),
+                source,
+            })
+        }
+        None => Ok(arg.to_string()),
+    }
+}
+
+fn read_content(arg: &str) -> ztr::Result<String> {
+    if arg != "-" {
+        return Ok(arg.to_string());
+    }
+
+    let mut content = String::new();
+    io::stdin().read_to_string(&mut content)?;
+    Ok(content)
+}
+
+fn parse_var(arg: &str) -> Result<(String, String), String> {
+    match arg.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("expected key=value, got '{}'", arg)),
+    }
+}
+
+fn read_vars_file(vars_path: &path::Path) -> ztr::Result<BTreeMap<String, Value>> {
+    let raw = fs::read_to_string(vars_path).map_err(|source| ztr::Error::File {
+        path: vars_path.to_path_buf(),
+        source,
+    })?;
+    serde_json::from_str(&raw).map_err(|e| {
+        ztr::Error::Config(format!(
+            "{}: expected a JSON object of variables, {}",
+            vars_path.display(),
+            e
+        ))
+    })
+}
+
+fn prompt_var(var: &ztr::TemplateVar) -> ztr::Result<Value> {
+    match &var.default {
+        Some(Value::String(default)) => eprint!("{} [{}]: ", var.name, default),
+        Some(default) => eprint!("{} [{}]: ", var.name, default),
+        None => eprint!("{}: ", var.name),
+    }
+    io::stderr().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    let answer = answer.trim_end_matches(['\r', '\n']);
+
+    Ok(match &var.default {
+        Some(default) if answer.is_empty() => default.clone(),
+        _ => Value::String(answer.to_string()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::prelude::*;
+
+    #[test]
+    fn test_read_vars_file_should_name_missing_file() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let vars_path = temp_dir.path().join("vars.json");
+
+        let error = read_vars_file(&vars_path).unwrap_err();
+
+        assert!(error
+            .to_string()
+            .starts_with(&format!("{}: ", vars_path.display())));
+    }
+
+    #[test]
+    fn test_read_template_should_name_missing_file() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let template_path = temp_dir.child("note.hbs");
+
+        let error = read_template(&format!("@{}", template_path.path().display())).unwrap_err();
+
+        assert!(matches!(error, ztr::Error::File { .. }));
+        assert!(error
+            .to_string()
+            .starts_with(&format!("{}: ", template_path.path().display())));
+    }
+}
@@ -0,0 +1,400 @@
+use crate::links::parse_links;
+use crate::ztr::{Error, Note, Result};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone};
+use serde_yaml::{Mapping, Value as Yaml};
+use std::collections::BTreeMap;
+use std::{fs, path};
+
+pub const FRONTMATTER_FENCE: &str = "---";
+
+/// Frontmatter keys that map onto [`Note`] fields, everything else ends up in `vars`.
+pub const KNOWN_KEYS: &[&str] = &["title", "tags", "created"];
+
+impl Note {
+    /// Reads the note at `note_path` along with its modification time. Without a `created`
+    /// key in the frontmatter the creation time of the file is used.
+    pub fn from_path(note_path: &path::Path) -> Result<Note> {
+        let raw = fs::read_to_string(note_path)?;
+        Note::from_file(note_path, &raw, &fs::metadata(note_path)?)
+    }
+
+    /// Like [`Note::from_path`], with the file already read.
+    pub fn from_file(note_path: &path::Path, raw: &str, metadata: &fs::Metadata) -> Result<Note> {
+        let filename = note_path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        let mut note = Note::parse(&filename, raw).map_err(|e| with_path(e, note_path))?;
+        note.path = Some(note_path.to_path_buf());
+        note.modified = metadata.modified().ok().map(DateTime::<Local>::from);
+        if note.created.is_none() {
+            note.created = metadata
+                .created()
+                .ok()
+                .map(DateTime::<Local>::from)
+                .or(note.modified);
+        }
+        Ok(note)
+    }
+
+    /// Splits the YAML frontmatter from the Markdown body of a note.
+    ///
+    /// The title is the frontmatter `title`, or else the first `# ` heading. Tags are the
+    /// frontmatter `tags` followed by inline `#[[tag]]`s. Unknown keys are kept in `vars`.
+    pub fn parse(filename: &str, raw: &str) -> Result<Note> {
+        let (frontmatter, content) = split_frontmatter(raw)?;
+
+        let title = match frontmatter.get("title") {
+            Some(Yaml::String(title)) => title.clone(),
+            _ => heading(content).unwrap_or_default().to_string(),
+        };
+
+        let mut tags = frontmatter_tags(&frontmatter);
+        for tag in inline_tags(content) {
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+
+        let created = frontmatter
+            .get("created")
+            .or(frontmatter.get("date"))
+            .and_then(Yaml::as_str)
+            .and_then(parse_date);
+
+        let mut vars = BTreeMap::new();
+        for (key, value) in &frontmatter {
+            let Some(key) = key.as_str().filter(|key| !KNOWN_KEYS.contains(key)) else {
+                continue;
+            };
+            let value = serde_json::to_value(value)
+                .map_err(|e| Error::Frontmatter(format!("{}: {}", key, e)))?;
+            vars.insert(key.to_string(), value);
+        }
+
+        let content_offset = raw[..raw.len() - content.len()].lines().count();
+        let links = parse_links(content)
+            .into_iter()
+            .map(|mut link| {
+                link.line += content_offset;
+                link
+            })
+            .collect();
+
+        Ok(Note {
+            vars,
+            template: String::new(),
+            filename: filename.to_string(),
+            id: filename.strip_suffix(".md").unwrap_or(filename).to_string(),
+            created,
+            modified: None,
+            title,
+            content: content.to_string(),
+            tags,
+            path: None,
+            content_offset,
+            links,
+            frontmatter,
+        })
+    }
+
+    /// Writes the note back as Markdown, keeping the frontmatter it was parsed from
+    /// with `title`, `tags`, `created` and `vars` updated in place.
+    pub fn to_markdown(&self) -> Result<String> {
+        let mut frontmatter = self.frontmatter.clone();
+
+        let has_key = |key: &str| self.frontmatter.contains_key(key);
+        if has_key("title") || heading(&self.content) != Some(self.title.as_str()) {
+            frontmatter.insert("title".into(), self.title.clone().into());
+        }
+
+        let inline = inline_tags(&self.content);
+        let parsed_tags = frontmatter_tags(&self.frontmatter);
+        let tags: Vec<String> = self
+            .tags
+            .iter()
+            .filter(|tag| !inline.contains(tag) || parsed_tags.contains(tag))
+            .cloned()
+            .collect();
+        if tags != parsed_tags && (has_key("tags") || !tags.is_empty()) {
+            let tags = tags.into_iter().map(Yaml::String).collect();
+            frontmatter.insert("tags".into(), Yaml::Sequence(tags));
+        }
+
+        let parsed_created = self
+            .frontmatter
+            .get("created")
+            .and_then(Yaml::as_str)
+            .and_then(parse_date);
+        if let Some(created) = self.created.filter(|_| has_key("created")) {
+            if Some(created) != parsed_created {
+                frontmatter.insert("created".into(), created.to_rfc3339().into());
+            }
+        }
+
+        frontmatter.retain(|key, _| {
+            key.as_str()
+                .is_some_and(|key| KNOWN_KEYS.contains(&key) || self.vars.contains_key(key))
+        });
+        for (key, value) in &self.vars {
+            let value = serde_yaml::to_value(value)
+                .map_err(|e| Error::Frontmatter(format!("{}: {}", key, e)))?;
+            frontmatter.insert(key.as_str().into(), value);
+        }
+
+        if frontmatter.is_empty() {
+            return Ok(self.content.clone());
+        }
+        let yaml =
+            serde_yaml::to_string(&frontmatter).map_err(|e| Error::Frontmatter(e.to_string()))?;
+        Ok(format!(
+            "{fence}\n{yaml}{fence}\n{content}",
+            fence = FRONTMATTER_FENCE,
+            yaml = yaml,
+            content = self.content
+        ))
+    }
+}
+
+fn with_path(e: Error, note_path: &path::Path) -> Error {
+    match e {
+        Error::Frontmatter(message) => {
+            Error::Frontmatter(format!("{}: {}", note_path.display(), message))
+        }
+        e => e,
+    }
+}
+
+/// Splits `raw` into its frontmatter and the body after the closing fence.
+/// A note without a leading `---` line has an empty frontmatter.
+pub fn split_frontmatter(raw: &str) -> Result<(Mapping, &str)> {
+    let Some(rest) = raw
+        .strip_prefix(FRONTMATTER_FENCE)
+        .and_then(|rest| rest.strip_prefix('\n').or(rest.strip_prefix("\r\n")))
+    else {
+        return Ok((Mapping::new(), raw));
+    };
+
+    let mut yaml_len = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == FRONTMATTER_FENCE {
+            let yaml = &rest[..yaml_len];
+            let frontmatter = match serde_yaml::from_str::<Yaml>(yaml) {
+                Ok(Yaml::Mapping(mapping)) => mapping,
+                Ok(Yaml::Null) => Mapping::new(),
+                Ok(_) => return Err(Error::Frontmatter(String::from("expected a mapping"))),
+                Err(e) => return Err(Error::Frontmatter(e.to_string())),
+            };
+            return Ok((frontmatter, &rest[yaml_len + line.len()..]));
+        }
+        yaml_len += line.len();
+    }
+
+    Err(Error::Frontmatter(format!(
+        "not closed with '{}'",
+        FRONTMATTER_FENCE
+    )))
+}
+
+/// Line number, counting from 1, where the body after the frontmatter starts.
+pub fn body_line(raw: &str) -> usize {
+    match split_frontmatter(raw) {
+        Ok((frontmatter, body)) if !frontmatter.is_empty() => {
+            raw[..raw.len() - body.len()].lines().count() + 1
+        }
+        _ => 1,
+    }
+}
+
+/// Text of the first `# ` heading in `content`.
+pub fn heading(content: &str) -> Option<&str> {
+    content
+        .lines()
+        .find_map(|line| line.strip_prefix("# "))
+        .map(str::trim)
+}
+
+/// Tags written inline as `#[[tag]]`, in order of appearance.
+pub fn inline_tags(content: &str) -> Vec<String> {
+    let mut tags: Vec<String> = vec![];
+    let mut rest = content;
+    while let Some(start) = rest.find("#[[") {
+        rest = &rest[start + 3..];
+        let Some(end) = rest.find("]]") else {
+            break;
+        };
+        let tag = rest[..end].trim();
+        if !tag.is_empty() && !tag.contains('\n') && !tags.iter().any(|t| t == tag) {
+            tags.push(tag.to_string());
+        }
+        rest = &rest[end + 2..];
+    }
+    tags
+}
+
+/// Tags as a YAML list, or a single string separated by commas or spaces.
+pub fn frontmatter_tags(frontmatter: &Mapping) -> Vec<String> {
+    match frontmatter.get("tags") {
+        Some(Yaml::Sequence(tags)) => tags
+            .iter()
+            .filter_map(|tag| match tag {
+                Yaml::String(tag) => Some(tag.clone()),
+                Yaml::Number(n) => Some(n.to_string()),
+                _ => None,
+            })
+            .collect(),
+        Some(Yaml::String(tags)) => tags
+            .split([',', ' '])
+            .filter(|tag| !tag.is_empty())
+            .map(String::from)
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// Reads RFC 3339 timestamps as well as local `YYYY-MM-DD[ HH:MM[:SS]]` dates.
+pub fn parse_date(raw: &str) -> Option<DateTime<Local>> {
+    let raw = raw.trim();
+    if let Ok(date) = DateTime::parse_from_rfc3339(raw) {
+        return Some(date.with_timezone(&Local));
+    }
+
+    let naive = ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(raw, format).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(raw, "%Y-%m-%d")
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        })?;
+    Local.from_local_datetime(&naive).earliest()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::prelude::*;
+
+    #[test]
+    fn test_parse_should_read_frontmatter_written_by_template() {
+        let raw = "---\ntags:\n  - test1\n  - test2\n---\n\n# test-title\n\ntest-content";
+
+        let note = Note::parse("test.md", raw).unwrap();
+
+        assert_eq!(note.id, "test");
+        assert_eq!(note.title, "test-title");
+        assert_eq!(note.tags, vec!["test1", "test2"]);
+        assert_eq!(note.content, "\n# test-title\n\ntest-content");
+    }
+
+    #[test]
+    fn test_parse_should_read_inline_tags_of_default_template() {
+        let raw = "# New zettle\n\nNew note content\n\n#[[fleeting]] #[[idea]] ";
+
+        let note = Note::parse("abc.md", raw).unwrap();
+
+        assert_eq!(note.title, "New zettle");
+        assert_eq!(note.tags, vec!["fleeting", "idea"]);
+        assert!(note.frontmatter.is_empty());
+    }
+
+    #[test]
+    fn test_parse_should_prefer_frontmatter_title() {
+        let raw = "---\ntitle: From frontmatter\ntags: a, b\n---\n# From heading\n";
+
+        let note = Note::parse("abc.md", raw).unwrap();
+
+        assert_eq!(note.title, "From frontmatter");
+        assert_eq!(note.tags, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_parse_should_keep_unknown_keys_in_vars() {
+        let raw = "---\nauthor: Luhmann\nyear: 1981\ncreated: 2026-01-02\n---\n";
+
+        let note = Note::parse("abc.md", raw).unwrap();
+
+        assert_eq!(note.vars["author"], serde_json::json!("Luhmann"));
+        assert_eq!(note.vars["year"], serde_json::json!(1981));
+        assert!(!note.vars.contains_key("created"));
+        assert_eq!(
+            note.created.unwrap().date_naive(),
+            NaiveDate::from_ymd_opt(2026, 1, 2).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_should_fail_on_invalid_frontmatter() {
+        let result = Note::parse("abc.md", "---\ntags: [a\n---\n");
+
+        assert!(matches!(result, Err(Error::Frontmatter(_))));
+    }
+
+    #[test]
+    fn test_parse_should_fail_on_unclosed_frontmatter() {
+        let result = Note::parse("abc.md", "---\ntitle: a\n");
+
+        assert!(matches!(result, Err(Error::Frontmatter(_))));
+    }
+
+    #[test]
+    fn test_to_markdown_should_round_trip_unknown_keys_in_order() {
+        let raw = "---\nzeta: 1\ntitle: Old\nalpha: two\n---\nbody\n";
+        let mut note = Note::parse("abc.md", raw).unwrap();
+        note.title = String::from("New");
+
+        let output = note.to_markdown().unwrap();
+
+        assert_eq!(output, "---\nzeta: 1\ntitle: New\nalpha: two\n---\nbody\n");
+    }
+
+    #[test]
+    fn test_to_markdown_should_leave_inline_tags_in_body() {
+        let raw = "# Title\n\n#[[fleeting]]";
+        let note = Note::parse("abc.md", raw).unwrap();
+
+        assert_eq!(note.to_markdown().unwrap(), raw);
+    }
+
+    #[test]
+    fn test_to_markdown_should_keep_unchanged_created_and_frontmatter_tags() {
+        let raw = "---\ncreated: 2026-01-02\ntags: [idea, fleeting]\n---\n# Title\n\n#[[fleeting]]";
+        let mut note = Note::parse("abc.md", raw).unwrap();
+
+        assert_eq!(
+            note.to_markdown().unwrap(),
+            "---\ncreated: 2026-01-02\ntags:\n- idea\n- fleeting\n---\n# Title\n\n#[[fleeting]]"
+        );
+
+        note.created = parse_date("2026-01-03");
+        note.tags.retain(|tag| tag != "idea");
+        let created = note.created.unwrap().to_rfc3339();
+        assert_eq!(
+            note.to_markdown().unwrap(),
+            format!(
+                "---\ncreated: {}\ntags:\n- fleeting\n---\n# Title\n\n#[[fleeting]]",
+                created
+            )
+        );
+    }
+
+    #[test]
+    fn test_body_line_should_skip_frontmatter() {
+        assert_eq!(body_line("---\ntags: [a]\n---\n# Title"), 4);
+        assert_eq!(body_line("# Title"), 1);
+    }
+
+    #[test]
+    fn test_from_path_should_fall_back_to_file_time() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        temp_dir.child("abc.md").write_str("# Title").unwrap();
+
+        let note = Note::from_path(&temp_dir.path().join("abc.md")).unwrap();
+
+        assert_eq!(note.filename, "abc.md");
+        assert_eq!(note.path, Some(temp_dir.path().join("abc.md")));
+        assert!(note.created.is_some());
+        assert!(note.modified.is_some());
+    }
+}
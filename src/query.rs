@@ -0,0 +1,759 @@
+use crate::tags::tag_matches;
+use crate::ztr::{Error, Graph, Note, Result, Vault};
+use chrono::{DateTime, Local, NaiveDate};
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// Columns printed when a query has no `SELECT`.
+const DEFAULT_FIELDS: &[&str] = &["id", "title", "tags"];
+
+const SECONDS_PER_DAY: f64 = 24.0 * 60.0 * 60.0;
+
+/// A parsed `[SELECT field, ...] [WHERE] condition [ORDER BY field [ASC|DESC], ...] [LIMIT n]`.
+#[derive(Debug, PartialEq)]
+pub struct Query {
+    pub fields: Vec<String>,
+    pub filter: Option<Expr>,
+    /// Fields to sort by, `true` for descending
+    pub order: Vec<(String, bool)>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Condition {
+        field: String,
+        op: Op,
+        value: String,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Op {
+    /// `:` or `=`, equal ignoring case
+    Eq,
+    /// `!=`
+    Ne,
+    /// `~`, contains ignoring case
+    Contains,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// Notes matching a query, in order, with the fields to show for them.
+pub struct QueryResult<'a> {
+    pub fields: Vec<String>,
+    pub notes: Vec<&'a Note>,
+    context: Context<'a>,
+}
+
+/// A value of a note's field, as compared and printed by queries.
+#[derive(Clone, Debug, PartialEq)]
+enum Scalar {
+    Text(String),
+    Number(f64),
+    Date(DateTime<Local>),
+    Bool(bool),
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Op(Op),
+    Open,
+    Close,
+    Comma,
+}
+
+/// Parses `query` and runs it against the notes of `vault`.
+pub fn query<'a>(vault: &'a Vault, query: &str) -> Result<QueryResult<'a>> {
+    let mut query = parse_query(query)?;
+    let context = Context::new(vault);
+    if let Some(filter) = &mut query.filter {
+        resolve_links(vault, filter);
+    }
+
+    let mut notes: Vec<&Note> = vault
+        .notes
+        .iter()
+        .filter(|note| {
+            query
+                .filter
+                .as_ref()
+                .is_none_or(|filter| context.matches(note, filter))
+        })
+        .collect();
+    notes.sort_by(|a, b| {
+        query
+            .order
+            .iter()
+            .map(|(field, descending)| {
+                let a = context.values(a, field).into_iter().next();
+                let b = context.values(b, field).into_iter().next();
+                let ordering = match (a, b) {
+                    (Some(a), Some(b)) => compare(&a, &b).unwrap_or(Ordering::Equal),
+                    // Notes without the field go last either way.
+                    (Some(_), None) => return Ordering::Less,
+                    (None, Some(_)) => return Ordering::Greater,
+                    (None, None) => Ordering::Equal,
+                };
+                match descending {
+                    true => ordering.reverse(),
+                    false => ordering,
+                }
+            })
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| a.id.cmp(&b.id))
+    });
+    notes.truncate(query.limit.unwrap_or(notes.len()));
+
+    Ok(QueryResult {
+        fields: match query.fields.is_empty() {
+            true => DEFAULT_FIELDS
+                .iter()
+                .map(|field| field.to_string())
+                .collect(),
+            false => query.fields,
+        },
+        notes,
+        context,
+    })
+}
+
+impl QueryResult<'_> {
+    /// The value of `field` for `note` as JSON: a list for tags and links, `null` when unset.
+    pub fn value(&self, note: &Note, field: &str) -> Value {
+        self.context.json(note, field)
+    }
+}
+
+/// Points `links-to` and `linked-from` conditions at the ID of the note they name,
+/// whether by ID, filename or title.
+fn resolve_links(vault: &Vault, expr: &mut Expr) {
+    match expr {
+        Expr::And(a, b) | Expr::Or(a, b) => {
+            resolve_links(vault, a);
+            resolve_links(vault, b);
+        }
+        Expr::Not(expr) => resolve_links(vault, expr),
+        Expr::Condition { field, value, .. } => {
+            if field == "links-to" || field == "linked-from" {
+                if let Ok(linked) = vault.resolve(value) {
+                    *value = linked.id.clone();
+                }
+            }
+        }
+    }
+}
+
+pub fn parse_query(query: &str) -> Result<Query> {
+    let mut parser = Parser {
+        tokens: tokenize(query)?,
+        position: 0,
+    };
+    let mut parsed = Query {
+        fields: vec![],
+        filter: None,
+        order: vec![],
+        limit: None,
+    };
+
+    if parser.keyword("SELECT") {
+        parsed.fields.push(parser.field()?);
+        while parser.next_if(&Token::Comma) {
+            parsed.fields.push(parser.field()?);
+        }
+    }
+    parser.keyword("WHERE");
+    if !parser.done() && !parser.peek_keyword("ORDER") && !parser.peek_keyword("LIMIT") {
+        parsed.filter = Some(parser.or()?);
+    }
+    if parser.keyword("ORDER") {
+        if !parser.keyword("BY") {
+            return Err(parser.error("expected BY after ORDER"));
+        }
+        loop {
+            let field = parser.field()?;
+            let descending = parser.keyword("DESC");
+            if !descending {
+                parser.keyword("ASC");
+            }
+            parsed.order.push((field, descending));
+            if !parser.next_if(&Token::Comma) {
+                break;
+            }
+        }
+    }
+    if parser.keyword("LIMIT") {
+        let limit = parser.field()?;
+        parsed.limit = Some(limit.parse().map_err(|_| {
+            Error::InvalidQuery(format!("LIMIT expects a number, got '{}'", limit))
+        })?);
+    }
+    if !parser.done() {
+        return Err(parser.error("unexpected"));
+    }
+    Ok(parsed)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn or(&mut self) -> Result<Expr> {
+        let mut expr = self.and()?;
+        while self.keyword("OR") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    /// Conditions joined by `AND`, or just written one after the other.
+    fn and(&mut self) -> Result<Expr> {
+        let mut expr = self.not()?;
+        loop {
+            let explicit = self.keyword("AND");
+            let ends = self.done()
+                || self.tokens[self.position] == Token::Close
+                || ["OR", "ORDER", "LIMIT"]
+                    .iter()
+                    .any(|k| self.peek_keyword(k));
+            if ends && !explicit {
+                return Ok(expr);
+            }
+            expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+        }
+    }
+
+    fn not(&mut self) -> Result<Expr> {
+        if self.keyword("NOT") {
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        if self.next_if(&Token::Open) {
+            let expr = self.or()?;
+            if !self.next_if(&Token::Close) {
+                return Err(self.error("expected ')'"));
+            }
+            return Ok(expr);
+        }
+
+        let field = self.field()?;
+        let Some(Token::Op(op)) = self.tokens.get(self.position) else {
+            return Err(Error::InvalidQuery(format!(
+                "expected an operator such as ':' or '>=' after '{}'",
+                field
+            )));
+        };
+        let op = *op;
+        self.position += 1;
+        let value = match self.tokens.get(self.position) {
+            Some(Token::Word(value)) | Some(Token::Quoted(value)) => value.clone(),
+            _ => return Err(self.error(&format!("expected a value for '{}'", field))),
+        };
+        self.position += 1;
+        Ok(Expr::Condition {
+            field: field.to_lowercase(),
+            op,
+            value,
+        })
+    }
+
+    fn field(&mut self) -> Result<String> {
+        match self.tokens.get(self.position) {
+            Some(Token::Word(word)) => {
+                self.position += 1;
+                Ok(word.clone())
+            }
+            _ => Err(self.error("expected a field name")),
+        }
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        let found = self.peek_keyword(keyword);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        matches!(self.tokens.get(self.position), Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword))
+    }
+
+    fn next_if(&mut self, token: &Token) -> bool {
+        let found = self.tokens.get(self.position) == Some(token);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn done(&self) -> bool {
+        self.position >= self.tokens.len()
+    }
+
+    fn error(&self, message: &str) -> Error {
+        match self.tokens.get(self.position) {
+            Some(token) => Error::InvalidQuery(format!("{} at {:?}", message, token)),
+            None => Error::InvalidQuery(format!("{} at the end", message)),
+        }
+    }
+}
+
+/// Splits `query` into words, quoted strings, operators, parentheses and commas. The value
+/// after an operator runs to the next space, so dates with times stay whole.
+fn tokenize(query: &str) -> Result<Vec<Token>> {
+    let mut tokens = vec![];
+    let mut chars = query.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let after_op = matches!(tokens.last(), Some(Token::Op(_)));
+        match c {
+            c if c.is_whitespace() => {}
+            '(' => tokens.push(Token::Open),
+            ')' => tokens.push(Token::Close),
+            ',' => tokens.push(Token::Comma),
+            '"' => {
+                let rest = &query[i + 1..];
+                let end = rest
+                    .find('"')
+                    .ok_or_else(|| Error::InvalidQuery(String::from("unclosed '\"'")))?;
+                tokens.push(Token::Quoted(rest[..end].to_string()));
+                for _ in 0..rest[..=end].chars().count() {
+                    chars.next();
+                }
+            }
+            ':' | '=' | '~' | '<' | '>' | '!' if !after_op => {
+                let followed_by_eq = chars.next_if(|(_, next)| *next == '=').is_some();
+                tokens.push(Token::Op(match (c, followed_by_eq) {
+                    (':', false) | ('=', false) => Op::Eq,
+                    ('~', false) => Op::Contains,
+                    ('<', false) => Op::Lt,
+                    ('<', true) => Op::Le,
+                    ('>', false) => Op::Gt,
+                    ('>', true) => Op::Ge,
+                    ('!', true) => Op::Ne,
+                    _ => {
+                        return Err(Error::InvalidQuery(format!(
+                            "unknown operator at '{}'",
+                            &query[i..]
+                        )))
+                    }
+                }));
+            }
+            _ => {
+                let is_end = |c: char| {
+                    c.is_whitespace()
+                        || matches!(c, '(' | ')' | ',')
+                        || (!after_op && matches!(c, ':' | '=' | '~' | '<' | '>' | '!'))
+                };
+                let mut end = i + c.len_utf8();
+                while let Some((j, next)) = chars.peek().copied() {
+                    if is_end(next) {
+                        break;
+                    }
+                    end = j + next.len_utf8();
+                    chars.next();
+                }
+                tokens.push(Token::Word(query[i..end].to_string()));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// What conditions on a vault need besides the notes themselves.
+struct Context<'a> {
+    vault: &'a Vault,
+    graph: Graph,
+    /// IDs of the notes linking to each note
+    incoming: HashMap<&'a str, Vec<String>>,
+}
+
+impl<'a> Context<'a> {
+    fn new(vault: &'a Vault) -> Self {
+        let graph = vault.graph();
+        let mut incoming: HashMap<&str, Vec<String>> = HashMap::new();
+        for note in &vault.notes {
+            incoming.insert(&note.id, vec![]);
+        }
+        for edge in graph.edges() {
+            let linking = edge
+                .target
+                .as_deref()
+                .and_then(|target| incoming.get_mut(target));
+            if let Some(linking) = linking {
+                if !linking.contains(&edge.source) {
+                    linking.push(edge.source.clone());
+                }
+            }
+        }
+        Context {
+            vault,
+            graph,
+            incoming,
+        }
+    }
+
+    fn matches(&self, note: &Note, expr: &Expr) -> bool {
+        match expr {
+            Expr::And(a, b) => self.matches(note, a) && self.matches(note, b),
+            Expr::Or(a, b) => self.matches(note, a) || self.matches(note, b),
+            Expr::Not(expr) => !self.matches(note, expr),
+            Expr::Condition { field, op, value }
+                if matches!(op, Op::Eq | Op::Ne) && is_tag_field(field) =>
+            {
+                let tagged = note.tags.iter().any(|tag| tag_matches(tag, value));
+                tagged == (*op == Op::Eq)
+            }
+            Expr::Condition { field, op, value } => {
+                let values = self.values(note, field);
+                match op {
+                    Op::Ne => !values.iter().any(|v| satisfies(v, Op::Eq, value)),
+                    op => values.iter().any(|v| satisfies(v, *op, value)),
+                }
+            }
+        }
+    }
+
+    fn values(&self, note: &Note, field: &str) -> Vec<Scalar> {
+        let text = |text: &str| vec![Scalar::Text(text.to_string())];
+        match field.to_lowercase().as_str() {
+            "id" => text(&note.id),
+            "filename" => text(&note.filename),
+            "path" => note
+                .path
+                .as_deref()
+                .and_then(|path| path.strip_prefix(&self.vault.root).ok())
+                .map(|path| text(&path.to_string_lossy()))
+                .unwrap_or_default(),
+            "title" => text(&note.title),
+            "content" => text(&note.content),
+            "tag" | "tags" => note
+                .tags
+                .iter()
+                .map(|tag| Scalar::Text(tag.clone()))
+                .collect(),
+            "created" => note.created.map(Scalar::Date).into_iter().collect(),
+            "modified" => note.modified.map(Scalar::Date).into_iter().collect(),
+            "words" => vec![Scalar::Number(
+                note.content.split_whitespace().count() as f64
+            )],
+            "age" => note
+                .created
+                .map(|created| {
+                    let age = Local::now().signed_duration_since(created);
+                    Scalar::Number(age.num_seconds() as f64 / SECONDS_PER_DAY)
+                })
+                .into_iter()
+                .collect(),
+            "links" => vec![Scalar::Number(
+                self.graph.outgoing.get(&note.id).map_or(0, Vec::len) as f64,
+            )],
+            "links-to" => self
+                .graph
+                .outgoing
+                .get(&note.id)
+                .into_iter()
+                .flatten()
+                .map(|edge| Scalar::Text(edge.target.clone().unwrap_or(edge.link.target.clone())))
+                .collect(),
+            "linked-from" => self
+                .incoming
+                .get(note.id.as_str())
+                .into_iter()
+                .flatten()
+                .map(|source| Scalar::Text(source.clone()))
+                .collect(),
+            "backlinks" => vec![Scalar::Number(
+                self.incoming.get(note.id.as_str()).map_or(0, Vec::len) as f64,
+            )],
+            field => note.vars.get(field).map(scalars).unwrap_or_default(),
+        }
+    }
+
+    fn json(&self, note: &Note, field: &str) -> Value {
+        let values: Vec<Value> = self
+            .values(note, field)
+            .into_iter()
+            .map(|value| match value {
+                Scalar::Text(text) => Value::String(text),
+                Scalar::Number(number) if number.fract() == 0.0 && number.abs() < 1e15 => {
+                    Value::from(number as i64)
+                }
+                Scalar::Number(number) => Value::from(number),
+                Scalar::Date(date) => Value::String(date.to_rfc3339()),
+                Scalar::Bool(flag) => Value::Bool(flag),
+            })
+            .collect();
+        let is_list = matches!(field, "tag" | "tags" | "links-to" | "linked-from")
+            || matches!(note.vars.get(field), Some(Value::Array(_)));
+        match is_list {
+            true => Value::Array(values),
+            false => values.into_iter().next().unwrap_or(Value::Null),
+        }
+    }
+}
+
+/// `tag:project` also matches descendants such as `project/ztr`.
+fn is_tag_field(field: &str) -> bool {
+    matches!(field.to_lowercase().as_str(), "tag" | "tags")
+}
+
+fn scalars(value: &Value) -> Vec<Scalar> {
+    match value {
+        Value::String(text) => vec![Scalar::Text(text.clone())],
+        Value::Number(number) => number.as_f64().map(Scalar::Number).into_iter().collect(),
+        Value::Bool(flag) => vec![Scalar::Bool(*flag)],
+        Value::Array(values) => values.iter().flat_map(scalars).collect(),
+        Value::Null | Value::Object(_) => vec![],
+    }
+}
+
+/// Whether `value op query` holds, comparing as numbers or dates when both sides are.
+/// A plain date compares against the day of a date with a time.
+fn satisfies(value: &Scalar, op: Op, query: &str) -> bool {
+    let ordering = match value {
+        Scalar::Number(number) => query
+            .parse::<f64>()
+            .ok()
+            .or_else(|| duration_days(query))
+            .and_then(|q| number.partial_cmp(&q)),
+        Scalar::Bool(flag) => query.parse::<bool>().ok().map(|q| flag.cmp(&q)),
+        Scalar::Date(date) => compare_date(date, query),
+        Scalar::Text(text) => {
+            if op == Op::Contains {
+                return text.to_lowercase().contains(&query.to_lowercase());
+            }
+            match (text.parse::<f64>(), query.parse::<f64>()) {
+                (Ok(number), Ok(q)) => number.partial_cmp(&q),
+                _ => match crate::ztr::parse_date(text) {
+                    Some(date) if crate::ztr::parse_date(query).is_some() => {
+                        compare_date(&date, query)
+                    }
+                    _ => Some(text.to_lowercase().cmp(&query.to_lowercase())),
+                },
+            }
+        }
+    };
+    let Some(ordering) = ordering else {
+        return false;
+    };
+    match op {
+        Op::Eq | Op::Contains => ordering.is_eq(),
+        Op::Ne => ordering.is_ne(),
+        Op::Lt => ordering.is_lt(),
+        Op::Le => ordering.is_le(),
+        Op::Gt => ordering.is_gt(),
+        Op::Ge => ordering.is_ge(),
+    }
+}
+
+/// Days in a duration such as `12h`, `7d`, `2w` or `1y`.
+pub fn duration_days(query: &str) -> Option<f64> {
+    let unit_start = query.find(|c: char| c.is_ascii_alphabetic())?;
+    let amount: f64 = query[..unit_start].parse().ok()?;
+    let days = match &query[unit_start..] {
+        "h" => 1.0 / 24.0,
+        "d" => 1.0,
+        "w" => 7.0,
+        "y" => 365.0,
+        _ => return None,
+    };
+    Some(amount * days)
+}
+
+fn compare_date(date: &DateTime<Local>, query: &str) -> Option<Ordering> {
+    match NaiveDate::parse_from_str(query.trim(), "%Y-%m-%d") {
+        Ok(day) => Some(date.date_naive().cmp(&day)),
+        Err(_) => crate::ztr::parse_date(query).map(|q| date.cmp(&q)),
+    }
+}
+
+fn compare(a: &Scalar, b: &Scalar) -> Option<Ordering> {
+    match (a, b) {
+        (Scalar::Number(a), Scalar::Number(b)) => a.partial_cmp(b),
+        (Scalar::Date(a), Scalar::Date(b)) => Some(a.cmp(b)),
+        (Scalar::Bool(a), Scalar::Bool(b)) => Some(a.cmp(b)),
+        (Scalar::Text(a), Scalar::Text(b)) => Some(a.to_lowercase().cmp(&b.to_lowercase())),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::prelude::*;
+
+    fn vault() -> (assert_fs::TempDir, Vault) {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        temp_dir
+            .child("a.md")
+            .write_str("---\ncreated: 2026-01-05\ntags: [project]\nstatus: open\npriority: 2\n---\n# Alpha\n[[c]] one two three")
+            .unwrap();
+        temp_dir
+            .child("b.md")
+            .write_str("---\ncreated: 2025-12-20\ntags: [project]\nstatus: done\npriority: 10\n---\n# Beta\n[[c]]")
+            .unwrap();
+        temp_dir
+            .child("c.md")
+            .write_str("---\ncreated: 2026-02-01T10:00:00\ntags: [idea]\n---\n# Gamma\n[[a]]")
+            .unwrap();
+
+        let vault = Vault::load(temp_dir.path()).unwrap();
+        (temp_dir, vault)
+    }
+
+    fn ids(vault: &Vault, query_text: &str) -> Vec<String> {
+        query(vault, query_text)
+            .unwrap()
+            .notes
+            .iter()
+            .map(|note| note.id.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_query_should_build_ast() {
+        let parsed = parse_query(
+            "SELECT id, title WHERE tag:project AND NOT (status:done OR words<3) ORDER BY created DESC, title LIMIT 5",
+        )
+        .unwrap();
+
+        let condition = |field: &str, op, value: &str| Expr::Condition {
+            field: field.to_string(),
+            op,
+            value: value.to_string(),
+        };
+        assert_eq!(
+            parsed,
+            Query {
+                fields: vec![String::from("id"), String::from("title")],
+                filter: Some(Expr::And(
+                    Box::new(condition("tag", Op::Eq, "project")),
+                    Box::new(Expr::Not(Box::new(Expr::Or(
+                        Box::new(condition("status", Op::Eq, "done")),
+                        Box::new(condition("words", Op::Lt, "3")),
+                    )))),
+                )),
+                order: vec![
+                    (String::from("created"), true),
+                    (String::from("title"), false)
+                ],
+                limit: Some(5),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_query_should_keep_values_with_colons_whole() {
+        let parsed = parse_query("created >= 2026-01-01T10:00:00 title~\"two words\"").unwrap();
+
+        assert_eq!(
+            parsed.filter,
+            Some(Expr::And(
+                Box::new(Expr::Condition {
+                    field: String::from("created"),
+                    op: Op::Ge,
+                    value: String::from("2026-01-01T10:00:00"),
+                }),
+                Box::new(Expr::Condition {
+                    field: String::from("title"),
+                    op: Op::Contains,
+                    value: String::from("two words"),
+                }),
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_query_should_reject_malformed_queries() {
+        for malformed in [
+            "tag",
+            "tag:",
+            "(tag:a",
+            "tag:a ORDER created",
+            "LIMIT x",
+            "\"open",
+        ] {
+            assert!(
+                matches!(parse_query(malformed), Err(Error::InvalidQuery(_))),
+                "{}",
+                malformed
+            );
+        }
+    }
+
+    #[test]
+    fn test_query_should_filter_by_tags_dates_and_frontmatter() {
+        let (_temp_dir, vault) = vault();
+
+        assert_eq!(
+            ids(
+                &vault,
+                "tag:project AND created>=2026-01-01 AND NOT status:done"
+            ),
+            vec!["a"]
+        );
+        assert_eq!(ids(&vault, "created=2026-02-01"), vec!["c"]);
+        assert_eq!(ids(&vault, "priority>5 OR tag:idea"), vec!["b", "c"]);
+        assert_eq!(ids(&vault, "NOT status:open"), vec!["b", "c"]);
+    }
+
+    #[test]
+    fn test_query_should_match_descendant_tags() {
+        let (temp_dir, _vault) = vault();
+        temp_dir
+            .child("d.md")
+            .write_str("---\ntags: [project/ztr]\n---\n# Delta")
+            .unwrap();
+        let vault = Vault::load(temp_dir.path()).unwrap();
+
+        assert_eq!(ids(&vault, "tag:project"), vec!["a", "b", "d"]);
+        assert_eq!(ids(&vault, "tag:project/ztr"), vec!["d"]);
+        assert_eq!(ids(&vault, "tag!=project"), vec!["c"]);
+    }
+
+    #[test]
+    fn test_query_should_filter_by_links_and_word_count() {
+        let (_temp_dir, vault) = vault();
+
+        assert_eq!(ids(&vault, "links-to:Gamma"), vec!["a", "b"]);
+        assert_eq!(ids(&vault, "linked-from:c"), vec!["a"]);
+        assert_eq!(ids(&vault, "words>=4"), vec!["a"]);
+    }
+
+    #[test]
+    fn test_query_should_compare_age_with_durations() {
+        let (_temp_dir, vault) = vault();
+
+        assert_eq!(ids(&vault, "age>7d"), vec!["a", "b", "c"]);
+        assert!(ids(&vault, "age<2w").is_empty());
+        assert_eq!(duration_days("36h"), Some(1.5));
+        assert_eq!(duration_days("7x"), None);
+    }
+
+    #[test]
+    fn test_query_should_order_limit_and_project() {
+        let (_temp_dir, vault) = vault();
+
+        let result = query(&vault, "SELECT id, priority ORDER BY priority DESC LIMIT 2").unwrap();
+
+        assert_eq!(result.fields, vec!["id", "priority"]);
+        let rows: Vec<Value> = result
+            .notes
+            .iter()
+            .map(|note| result.value(note, "priority"))
+            .collect();
+        assert_eq!(rows, vec![Value::from(10), Value::from(2)]);
+    }
+}
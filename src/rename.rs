@@ -0,0 +1,561 @@
+use crate::atomic;
+use crate::id::note_ids;
+use crate::links::Link;
+use crate::ztr::{Error, LinkKind, Note, Result, Vault};
+use serde_yaml::Value as Yaml;
+use std::collections::BTreeMap;
+use std::{fs, io, path};
+
+/// A rename of a note along with the rewrites of every link to it, prepared by
+/// [`plan_move`] and carried out by [`Move::apply`].
+#[derive(Debug)]
+pub struct Move {
+    pub from: path::PathBuf,
+    pub to: path::PathBuf,
+    /// Every file whose content changes, the moved note included
+    pub changes: Vec<FileChange>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct FileChange {
+    /// Where the file is written, the new path for the moved note
+    pub path: path::PathBuf,
+    pub before: String,
+    pub after: String,
+}
+
+/// Plans moving `note` to `new_name`, a filename in the note's directory or a path below the
+/// vault root when it contains a `/`. Links by filename or ID are pointed at the new name,
+/// and with `title` the note's title changes along with links written as the old title.
+pub fn plan_move(vault: &Vault, note: &Note, new_name: &str, title: Option<&str>) -> Result<Move> {
+    let from = note
+        .path
+        .clone()
+        .ok_or_else(|| Error::NotFound(format!("note '{}' has no file", note.id)))?;
+    let to = destination(vault, &from, new_name)?;
+    let new_filename = to
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let new_id = new_filename.strip_suffix(".md").unwrap_or(&new_filename);
+    let renamed_title = title.filter(|title| *title != note.title);
+
+    let graph = vault.graph();
+    let mut rewrites: BTreeMap<&str, Vec<(Link, String)>> = BTreeMap::new();
+    for edge in graph.backlinks(&note.id) {
+        let written = edge
+            .link
+            .target
+            .rsplit('/')
+            .next()
+            .unwrap_or(&edge.link.target);
+        let replacement = if written == note.filename || written == note.id {
+            match edge.link.kind {
+                LinkKind::Markdown => {
+                    let source_dir = match edge.source == note.id {
+                        true => to.parent(),
+                        false => source_path(vault, &edge.source).and_then(path::Path::parent),
+                    };
+                    relative_link(source_dir.unwrap_or(&vault.root), &to)
+                }
+                LinkKind::Wiki if written.ends_with(".md") => new_filename.clone(),
+                LinkKind::Wiki => new_id.to_string(),
+            }
+        } else {
+            match renamed_title {
+                Some(title) if edge.link.target.to_lowercase() == note.title.to_lowercase() => {
+                    title.to_string()
+                }
+                _ => continue,
+            }
+        };
+        rewrites
+            .entry(&edge.source)
+            .or_default()
+            .push((edge.link.clone(), replacement));
+    }
+    // Markdown links of the note itself are relative to its directory.
+    if from.parent() != to.parent() {
+        for edge in graph.outgoing[&note.id].iter() {
+            let target_path = match edge.target.as_deref() {
+                Some(target) if target != note.id && edge.link.kind == LinkKind::Markdown => {
+                    source_path(vault, target)
+                }
+                _ => None,
+            };
+            if let Some(target_path) = target_path {
+                let replacement = relative_link(to.parent().unwrap_or(&vault.root), target_path);
+                rewrites
+                    .entry(&note.id)
+                    .or_default()
+                    .push((edge.link.clone(), replacement));
+            }
+        }
+    }
+
+    let mut changes = vec![];
+    for source in vault.notes.iter().filter(|source| source.id != note.id) {
+        let Some(links) = rewrites.get(source.id.as_str()) else {
+            continue;
+        };
+        let Some(source_path) = &source.path else {
+            continue;
+        };
+        let before = fs::read_to_string(source_path)?;
+        let after = rewrite_links(&before, links);
+        if after != before {
+            changes.push(FileChange {
+                path: source_path.clone(),
+                before,
+                after,
+            });
+        }
+    }
+
+    let before = fs::read_to_string(&from)?;
+    let mut after = rewrite_links(&before, rewrites.get(note.id.as_str()).unwrap_or(&vec![]));
+    let renamed_id = note.vars.get("id").and_then(|id| id.as_str()) == Some(note.id.as_str());
+    if renamed_title.is_some() || renamed_id {
+        let mut moved = Note::parse(&new_filename, &after)?;
+        if let Some(title) = renamed_title {
+            if !moved.frontmatter.contains_key("title") {
+                moved.content = retitle_heading(&moved.content, &note.title, title);
+            }
+            moved.title = title.to_string();
+        }
+        if renamed_id {
+            moved.vars.insert(String::from("id"), new_id.into());
+            moved.frontmatter.insert("id".into(), Yaml::from(new_id));
+        }
+        after = moved.to_markdown()?;
+    }
+    changes.insert(
+        0,
+        FileChange {
+            path: to.clone(),
+            before,
+            after,
+        },
+    );
+
+    Ok(Move { from, to, changes })
+}
+
+impl Move {
+    /// Writes the changes with [`write_changes`] and then removes the note's old file,
+    /// unless the note stays where it is. When that fails the changes are undone.
+    pub fn apply(&self) -> Result<()> {
+        if let Some(dir) = self.to.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        write_changes(&self.changes)?;
+        if self.from != self.to {
+            if let Err(e) = fs::remove_file(&self.from) {
+                let written: Vec<(&FileChange, bool)> = self
+                    .changes
+                    .iter()
+                    .map(|change| (change, change.path != self.to))
+                    .collect();
+                undo(&written);
+                return Err(e.into());
+            }
+        }
+        Ok(())
+    }
+
+    /// The changes as a diff of the lines that differ, with paths relative to `zk_root`.
+    pub fn diff(&self, zk_root: &path::Path) -> String {
+        let relative = |file: &path::Path| {
+            file.strip_prefix(zk_root)
+                .unwrap_or(file)
+                .display()
+                .to_string()
+        };
+        let mut diff = String::new();
+        for (i, change) in self.changes.iter().enumerate() {
+            let before_path = match i {
+                0 => relative(&self.from),
+                _ => relative(&change.path),
+            };
+            diff.push_str(&change.diff(&before_path, &relative(&change.path)));
+        }
+        diff
+    }
+}
+
+impl FileChange {
+    /// The lines that differ, under a `---`/`+++` header naming the file before and after.
+    pub fn diff(&self, before_name: &str, after_name: &str) -> String {
+        format!(
+            "--- {}\n+++ {}\n{}",
+            before_name,
+            after_name,
+            line_diff(&self.before, &self.after)
+        )
+    }
+}
+
+/// Writes every change next to its file first and only then renames them all into place.
+/// When a rename fails, the files already renamed get their content from before back, so a
+/// failure part way leaves the vault as it was unless undoing fails as well.
+pub fn write_changes(changes: &[FileChange]) -> Result<()> {
+    let mut staged: Vec<(path::PathBuf, &FileChange, bool)> = vec![];
+    for change in changes {
+        match atomic::stage(&change.path, &change.after) {
+            Ok(temp) => staged.push((temp, change, change.path.exists())),
+            Err(e) => {
+                for (temp, _, _) in &staged {
+                    let _ = fs::remove_file(temp);
+                }
+                return Err(e.into());
+            }
+        }
+    }
+
+    for (i, (temp, change, _)) in staged.iter().enumerate() {
+        if let Err(e) = fs::rename(temp, &change.path) {
+            for (temp, _, _) in &staged[i..] {
+                let _ = fs::remove_file(temp);
+            }
+            let renamed: Vec<(&FileChange, bool)> = staged[..i]
+                .iter()
+                .map(|(_, change, existed)| (*change, *existed))
+                .collect();
+            undo(&renamed);
+            return Err(e.into());
+        }
+    }
+    Ok(())
+}
+
+/// Puts back what was in the files of `written` before: the content of those that existed
+/// and no file for the others.
+fn undo(written: &[(&FileChange, bool)]) {
+    for (change, existed) in written {
+        let _ = match existed {
+            true => atomic::write(&change.path, &change.before),
+            false => fs::remove_file(&change.path),
+        };
+    }
+}
+
+/// Path of the note moved from `from` to `new_name`, which must not exist yet.
+fn destination(vault: &Vault, from: &path::Path, new_name: &str) -> Result<path::PathBuf> {
+    let new_name = new_name.trim();
+    let filename = match new_name.ends_with(".md") {
+        true => new_name.to_string(),
+        false => format!("{}.md", new_name),
+    };
+    if new_name.is_empty() || new_name.ends_with('/') || filename == ".md" {
+        return Err(Error::InvalidId(format!(
+            "'{}' is not a note name",
+            new_name
+        )));
+    }
+
+    let to = match filename.contains('/') {
+        true => vault.root.join(filename),
+        false => from.with_file_name(filename),
+    };
+    if to.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists", to.display()),
+        )
+        .into());
+    }
+    // IDs are unique vault-wide, so the new name must not be taken in another directory.
+    let new_id = to
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string());
+    let old_id = from
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string());
+    if let Some(new_id) = new_id.filter(|new_id| Some(new_id) != old_id.as_ref()) {
+        if note_ids(&vault.root)?.contains(&new_id) {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("a note with the ID '{}' already exists", new_id),
+            )
+            .into());
+        }
+    }
+    Ok(to)
+}
+
+fn source_path<'a>(vault: &'a Vault, id: &str) -> Option<&'a path::Path> {
+    vault
+        .notes
+        .iter()
+        .find(|note| note.id == id)
+        .and_then(|note| note.path.as_deref())
+}
+
+/// `target` relative to the directory `from`, as written in a Markdown link.
+fn relative_link(from: &path::Path, target: &path::Path) -> String {
+    let from: Vec<_> = from.components().collect();
+    let target: Vec<_> = target.components().collect();
+    let common = from.iter().zip(&target).take_while(|(a, b)| a == b).count();
+
+    let mut parts: Vec<String> = vec![String::from(".."); from.len() - common];
+    parts.extend(
+        target[common..]
+            .iter()
+            .map(|part| part.as_os_str().to_string_lossy().to_string()),
+    );
+    parts.join("/").replace(' ', "%20")
+}
+
+/// `raw` with the target of every link in `links` replaced, links counting lines from 1.
+fn rewrite_links(raw: &str, links: &[(Link, String)]) -> String {
+    let mut lines: Vec<String> = raw.split_inclusive('\n').map(String::from).collect();
+    let mut links: Vec<&(Link, String)> = links.iter().collect();
+    links.sort_by_key(|(link, _)| (link.line, std::cmp::Reverse(link.span.start)));
+    for (link, replacement) in links {
+        if let Some(line) = lines.get_mut(link.line - 1) {
+            line.replace_range(link.span.clone(), replacement);
+        }
+    }
+    lines.concat()
+}
+
+/// `content` with its `# old` heading renamed to `# new`.
+fn retitle_heading(content: &str, old: &str, new: &str) -> String {
+    let mut done = false;
+    content
+        .split_inclusive('\n')
+        .map(|line| match line.strip_prefix("# ") {
+            Some(heading) if !done && heading.trim() == old => {
+                done = true;
+                line.replacen(old, new, 1)
+            }
+            _ => line.to_string(),
+        })
+        .collect()
+}
+
+/// Hunks of the lines that differ between `before` and `after`, found through their
+/// longest common subsequence.
+fn line_diff(before: &str, after: &str) -> String {
+    let old: Vec<&str> = before.lines().collect();
+    let new: Vec<&str> = after.lines().collect();
+    let mut common = vec![vec![0; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = match old[i] == new[j] {
+                true => common[i + 1][j + 1] + 1,
+                false => common[i + 1][j].max(common[i][j + 1]),
+            };
+        }
+    }
+
+    let mut diff = String::new();
+    let mut hunk: Vec<String> = vec![];
+    let (mut i, mut j) = (0, 0);
+    let (mut hunk_i, mut hunk_j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            flush_hunk(&mut diff, &mut hunk, hunk_i, hunk_j);
+            i += 1;
+            j += 1;
+            continue;
+        }
+        if hunk.is_empty() {
+            (hunk_i, hunk_j) = (i + 1, j + 1);
+        }
+        if j < new.len() && (i == old.len() || common[i][j + 1] >= common[i + 1][j]) {
+            hunk.push(format!("+{}", new[j]));
+            j += 1;
+        } else {
+            hunk.push(format!("-{}", old[i]));
+            i += 1;
+        }
+    }
+    flush_hunk(&mut diff, &mut hunk, hunk_i, hunk_j);
+    diff
+}
+
+fn flush_hunk(diff: &mut String, hunk: &mut Vec<String>, old_line: usize, new_line: usize) {
+    if hunk.is_empty() {
+        return;
+    }
+    // Removals first, as in a unified diff.
+    hunk.sort_by_key(|line| !line.starts_with('-'));
+    diff.push_str(&format!("@@ -{} +{} @@\n", old_line, new_line));
+    for line in hunk.drain(..) {
+        diff.push_str(&line);
+        diff.push('\n');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::prelude::*;
+
+    fn vault() -> (assert_fs::TempDir, Vault) {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        temp_dir
+            .child("old.md")
+            .write_str("# Old\n[[b]] [d](d.md)\n")
+            .unwrap();
+        temp_dir
+            .child("b.md")
+            .write_str("# Beta\n[[old]] [[old#Part|label]] [[Old]]\n")
+            .unwrap();
+        temp_dir
+            .child("sub/c.md")
+            .write_str("See [it](../old.md) and [[old.md]].\n")
+            .unwrap();
+        temp_dir
+            .child("d.md")
+            .write_str("# Delta\n[[b]]\n")
+            .unwrap();
+
+        let vault = Vault::load(temp_dir.path()).unwrap();
+        (temp_dir, vault)
+    }
+
+    #[test]
+    fn test_plan_move_should_rewrite_links_by_id_and_filename() {
+        let (temp_dir, vault) = vault();
+
+        let planned = plan_move(&vault, vault.resolve("old").unwrap(), "new", None).unwrap();
+
+        let changed: Vec<(&path::Path, &str)> = planned
+            .changes
+            .iter()
+            .map(|change| {
+                (
+                    change.path.strip_prefix(temp_dir.path()).unwrap(),
+                    change.after.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            changed,
+            vec![
+                (path::Path::new("new.md"), "# Old\n[[b]] [d](d.md)\n"),
+                (
+                    path::Path::new("b.md"),
+                    "# Beta\n[[new]] [[new#Part|label]] [[Old]]\n"
+                ),
+                (
+                    path::Path::new("sub/c.md"),
+                    "See [it](../new.md) and [[new.md]].\n"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_plan_move_should_retitle_note_and_title_links() {
+        let (_temp_dir, vault) = vault();
+
+        let planned = plan_move(
+            &vault,
+            vault.resolve("old").unwrap(),
+            "sub/new",
+            Some("New"),
+        )
+        .unwrap();
+
+        assert!(planned.to.ends_with("sub/new.md"));
+        assert_eq!(planned.changes[0].after, "# New\n[[b]] [d](../d.md)\n");
+        assert_eq!(
+            planned.changes[1].after,
+            "# Beta\n[[new]] [[new#Part|label]] [[New]]\n"
+        );
+        assert_eq!(
+            planned.changes[2].after,
+            "See [it](new.md) and [[new.md]].\n"
+        );
+    }
+
+    #[test]
+    fn test_plan_move_should_refuse_existing_destination() {
+        let (_temp_dir, vault) = vault();
+
+        let result = plan_move(&vault, vault.resolve("old").unwrap(), "d", None);
+
+        assert!(matches!(result, Err(Error::Io(e)) if e.kind() == io::ErrorKind::AlreadyExists));
+    }
+
+    #[test]
+    fn test_plan_move_should_refuse_id_used_in_other_directory() {
+        let (_temp_dir, vault) = vault();
+
+        let result = plan_move(&vault, vault.resolve("old").unwrap(), "sub/d", None);
+
+        assert!(matches!(result, Err(Error::Io(e)) if e.kind() == io::ErrorKind::AlreadyExists));
+        assert!(plan_move(&vault, vault.resolve("old").unwrap(), "sub/old", None).is_ok());
+    }
+
+    #[test]
+    fn test_apply_should_move_note_and_write_links() {
+        let (temp_dir, vault) = vault();
+        let planned = plan_move(&vault, vault.resolve("old").unwrap(), "new", None).unwrap();
+
+        planned.apply().unwrap();
+
+        assert!(!temp_dir.child("old.md").exists());
+        temp_dir.child("new.md").assert("# Old\n[[b]] [d](d.md)\n");
+        temp_dir
+            .child("b.md")
+            .assert("# Beta\n[[new]] [[new#Part|label]] [[Old]]\n");
+        let vault = Vault::load(temp_dir.path()).unwrap();
+        assert_eq!(vault.graph().unresolved().count(), 0);
+    }
+
+    #[test]
+    fn test_write_changes_should_undo_renamed_files_when_a_rename_fails() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        temp_dir.child("a.md").write_str("A").unwrap();
+        temp_dir.child("b.md/keep").write_str("").unwrap();
+        let change = |name: &str, before: &str| FileChange {
+            path: temp_dir.path().join(name),
+            before: before.to_string(),
+            after: String::from("changed"),
+        };
+
+        let result = write_changes(&[
+            change("a.md", "A"),
+            change("new.md", ""),
+            change("b.md", ""),
+        ]);
+
+        assert!(result.is_err());
+        temp_dir.child("a.md").assert("A");
+        assert!(!temp_dir.child("new.md").exists());
+        assert!(!temp_dir.child(".b.md.ztr-tmp").exists());
+    }
+
+    #[test]
+    fn test_apply_should_undo_changes_when_old_file_cannot_be_removed() {
+        let (temp_dir, vault) = vault();
+        let planned = plan_move(&vault, vault.resolve("old").unwrap(), "new", None).unwrap();
+        fs::remove_file(temp_dir.path().join("old.md")).unwrap();
+
+        let result = planned.apply();
+
+        assert!(result.is_err());
+        assert!(!temp_dir.child("new.md").exists());
+        temp_dir
+            .child("b.md")
+            .assert("# Beta\n[[old]] [[old#Part|label]] [[Old]]\n");
+    }
+
+    #[test]
+    fn test_diff_should_show_changed_lines() {
+        let (temp_dir, vault) = vault();
+        let planned = plan_move(&vault, vault.resolve("old").unwrap(), "new", None).unwrap();
+
+        let diff = planned.diff(temp_dir.path());
+
+        assert!(diff.starts_with("--- old.md\n+++ new.md\n--- b.md\n+++ b.md\n"));
+        assert!(diff.contains(
+            "@@ -2 +2 @@\n-[[old]] [[old#Part|label]] [[Old]]\n+[[new]] [[new#Part|label]] [[Old]]\n"
+        ));
+    }
+}
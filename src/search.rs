@@ -0,0 +1,549 @@
+use crate::index::load_derived;
+use crate::ztr::{Error, Note, Result, Vault};
+use serde_derive::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ops::Range;
+
+const K1: f64 = 1.2;
+const B: f64 = 0.75;
+const SNIPPET_WIDTH: usize = 160;
+
+/// Bumped whenever the layout of [`Terms`] changes, older caches are rebuilt.
+const TERMS_VERSION: u32 = 1;
+
+/// A note matching a search, best first.
+#[derive(Debug)]
+pub struct SearchHit<'a> {
+    pub note: &'a Note,
+    pub score: f64,
+    /// The first body line with a match, or the first line of the body
+    pub snippet: String,
+    /// Bytes of `snippet` that matched the query
+    pub highlights: Vec<Range<usize>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Field {
+    Title,
+    Tags,
+    Body,
+}
+
+impl Field {
+    const ALL: [Field; 3] = [Field::Title, Field::Tags, Field::Body];
+
+    /// How much a match in the field counts compared to one in the body.
+    fn boost(self) -> f64 {
+        match self {
+            Field::Title => 3.0,
+            Field::Tags => 2.0,
+            Field::Body => 1.0,
+        }
+    }
+}
+
+/// Part of a search query: a word, a `prefix*` or a `"phrase"`, any of them negated by `-`.
+#[derive(Debug, PartialEq)]
+enum Clause {
+    Word(String),
+    Prefix(String),
+    Phrase(Vec<String>),
+}
+
+#[derive(Debug, PartialEq)]
+struct Query {
+    required: Vec<Clause>,
+    excluded: Vec<Clause>,
+}
+
+/// How often each term occurs in the title, tags and body of a note, as `term:count` words
+/// sorted by term, one string per [`Field`]. Cached in `.ztr/search` so that only changed notes
+/// are read and tokenized again; plain strings load much faster than a map per note.
+#[derive(Serialize, Deserialize, Clone, Default)]
+struct Terms {
+    counts: [String; 3],
+    /// Boosted number of terms
+    length: f64,
+}
+
+impl Terms {
+    fn of(note: &Note) -> Terms {
+        let mut terms = Terms::default();
+        for field in Field::ALL {
+            let mut counts: BTreeMap<String, u32> = BTreeMap::new();
+            for (term, _) in tokenize(&field_text(note, field, &note.content)) {
+                *counts.entry(term).or_default() += 1;
+                terms.length += field.boost();
+            }
+            terms.counts[field as usize] = counts
+                .into_iter()
+                .map(|(term, count)| format!("{}:{}", term, count))
+                .collect::<Vec<_>>()
+                .join(" ");
+        }
+        terms
+    }
+
+    /// Each term of `field` with how often it occurs, in order.
+    fn counts(&self, field: Field) -> impl Iterator<Item = (&str, u32)> {
+        self.counts[field as usize].split(' ').filter_map(|word| {
+            let (term, count) = word.split_once(':')?;
+            Some((term, count.parse().ok()?))
+        })
+    }
+
+    /// Boosted number of times terms matching `matches` occur, where `matches` tells how a
+    /// term compares to the ones it is after, to stop early.
+    fn frequency(&self, matches: impl Fn(&str) -> Ordering) -> f64 {
+        Field::ALL
+            .iter()
+            .map(|field| {
+                self.counts(*field)
+                    .skip_while(|(term, _)| matches(term) == Ordering::Less)
+                    .take_while(|(term, _)| matches(term) == Ordering::Equal)
+                    .map(|(_, count)| count)
+                    .sum::<u32>() as f64
+                    * field.boost()
+            })
+            .sum()
+    }
+}
+
+/// Term counts of a set of notes, to rank them with.
+struct SearchIndex<'a> {
+    notes: &'a [Note],
+    terms: Vec<Terms>,
+    average_length: f64,
+}
+
+/// Ranks the notes of `vault` against `query` with BM25, boosting matches in titles and tags,
+/// and returns the best `limit` of them. Every word, prefix and phrase must match, and no
+/// negated one may. Bodies are only read for phrases and snippets, as term counts come from
+/// `.ztr/search`, so the vault may be loaded without them.
+pub fn search<'a>(vault: &'a Vault, query: &str, limit: usize) -> Result<Vec<SearchHit<'a>>> {
+    let query = parse_query(query)?;
+    let index = SearchIndex::build(vault);
+
+    let mut scores: Option<HashMap<usize, f64>> = None;
+    for clause in &query.required {
+        // Over every note, as how many notes match weighs in on the score.
+        let frequencies = index.frequencies(clause, None)?;
+        let idf = index.idf(frequencies.len());
+        let clause_scores = frequencies
+            .into_iter()
+            .map(|(doc, frequency)| (doc, idf * index.saturate(doc, frequency)));
+        scores = Some(match scores {
+            None => clause_scores.collect(),
+            Some(scores) => clause_scores
+                .filter_map(|(doc, score)| scores.get(&doc).map(|total| (doc, total + score)))
+                .collect(),
+        });
+    }
+    let mut scores = scores.unwrap_or_default();
+    for clause in &query.excluded {
+        for doc in index.frequencies(clause, Some(&scores))?.keys() {
+            scores.remove(doc);
+        }
+    }
+
+    let mut ranked: Vec<(usize, f64)> = scores.into_iter().collect();
+    ranked.sort_by(|(a, a_score), (b, b_score)| {
+        b_score
+            .total_cmp(a_score)
+            .then_with(|| vault.notes[*a].id.cmp(&vault.notes[*b].id))
+    });
+    ranked.truncate(limit);
+    ranked
+        .into_iter()
+        .map(|(doc, score)| {
+            let note = &vault.notes[doc];
+            let (snippet, highlights) = snippet(&body(note)?, &query.required);
+            Ok(SearchHit {
+                note,
+                score,
+                snippet,
+                highlights,
+            })
+        })
+        .collect()
+}
+
+impl<'a> SearchIndex<'a> {
+    fn build(vault: &'a Vault) -> Self {
+        let terms = load_derived(vault, "search", TERMS_VERSION, Terms::of);
+        let average_length =
+            terms.iter().map(|terms| terms.length).sum::<f64>() / (terms.len().max(1) as f64);
+        SearchIndex {
+            notes: &vault.notes,
+            terms,
+            average_length,
+        }
+    }
+
+    /// Boosted number of times `clause` occurs in each note it occurs in, out of `among`
+    /// when given.
+    fn frequencies(
+        &self,
+        clause: &Clause,
+        among: Option<&HashMap<usize, f64>>,
+    ) -> Result<HashMap<usize, f64>> {
+        let docs = self
+            .terms
+            .iter()
+            .enumerate()
+            .filter(|(doc, _)| among.is_none_or(|among| among.contains_key(doc)));
+        let mut frequencies = HashMap::new();
+        for (doc, terms) in docs {
+            let frequency = match clause {
+                Clause::Word(word) => terms.frequency(|term| term.cmp(word)),
+                Clause::Prefix(prefix) => {
+                    terms.frequency(|term| match term.starts_with(prefix.as_str()) {
+                        true => Ordering::Equal,
+                        false => term.cmp(prefix),
+                    })
+                }
+                // Counts only tell which notes may hold the phrase, their text tells for sure.
+                Clause::Phrase(words)
+                    if words
+                        .iter()
+                        .all(|word| terms.frequency(|term| term.cmp(word)) > 0.0) =>
+                {
+                    let note = &self.notes[doc];
+                    let body = body(note)?;
+                    Field::ALL
+                        .iter()
+                        .map(|field| {
+                            let tokens: Vec<String> = tokenize(&field_text(note, *field, &body))
+                                .into_iter()
+                                .map(|(term, _)| term)
+                                .collect();
+                            let count = tokens
+                                .windows(words.len())
+                                .filter(|window| *window == words.as_slice())
+                                .count();
+                            count as f64 * field.boost()
+                        })
+                        .sum()
+                }
+                Clause::Phrase(_) => 0.0,
+            };
+            if frequency > 0.0 {
+                frequencies.insert(doc, frequency);
+            }
+        }
+        Ok(frequencies)
+    }
+
+    fn idf(&self, matching: usize) -> f64 {
+        let total = self.notes.len() as f64;
+        let matching = matching as f64;
+        (1.0 + (total - matching + 0.5) / (matching + 0.5)).ln()
+    }
+
+    fn saturate(&self, doc: usize, frequency: f64) -> f64 {
+        let relative_length = self.terms[doc].length / self.average_length.max(1.0);
+        frequency * (K1 + 1.0) / (frequency + K1 * (1.0 - B + B * relative_length))
+    }
+}
+
+/// The text of `field` of `note`, whose body is `body`.
+fn field_text<'a>(note: &'a Note, field: Field, body: &'a str) -> Cow<'a, str> {
+    match field {
+        Field::Title => Cow::Borrowed(&note.title),
+        Field::Tags => Cow::Owned(note.tags.join(" ")),
+        Field::Body => Cow::Borrowed(body),
+    }
+}
+
+/// The body of `note`, read from its file when it was loaded without it.
+fn body(note: &Note) -> Result<Cow<'_, str>> {
+    match &note.path {
+        Some(note_path) if note.content.is_empty() => {
+            Ok(Cow::Owned(Note::from_path(note_path)?.content))
+        }
+        _ => Ok(Cow::Borrowed(&note.content)),
+    }
+}
+
+fn parse_query(query: &str) -> Result<Query> {
+    let mut parsed = Query {
+        required: vec![],
+        excluded: vec![],
+    };
+    let mut rest = query.trim_start();
+    while !rest.is_empty() {
+        let (negated, part) = match rest.strip_prefix('-') {
+            Some(part) => (true, part),
+            None => (false, rest),
+        };
+
+        let clause;
+        if let Some(quoted) = part.strip_prefix('"') {
+            let end = quoted
+                .find('"')
+                .ok_or_else(|| Error::InvalidQuery(String::from("unclosed '\"'")))?;
+            let words: Vec<String> = tokenize(&quoted[..end])
+                .into_iter()
+                .map(|(word, _)| word)
+                .collect();
+            clause = (!words.is_empty()).then_some(Clause::Phrase(words));
+            rest = &quoted[end + 1..];
+        } else {
+            let end = part.find(char::is_whitespace).unwrap_or(part.len());
+            let word = &part[..end];
+            let words: Vec<String> = tokenize(word).into_iter().map(|(word, _)| word).collect();
+            clause = match words.len() {
+                0 => None,
+                1 if word.ends_with('*') => Some(Clause::Prefix(words[0].clone())),
+                1 => Some(Clause::Word(words[0].clone())),
+                _ => Some(Clause::Phrase(words)),
+            };
+            rest = &part[end..];
+        }
+
+        match clause {
+            Some(clause) if negated => parsed.excluded.push(clause),
+            Some(clause) => parsed.required.push(clause),
+            None => {}
+        }
+        rest = rest.trim_start();
+    }
+
+    if parsed.required.is_empty() {
+        return Err(Error::InvalidQuery(String::from(
+            "nothing to search for, only excluded terms",
+        )));
+    }
+    Ok(parsed)
+}
+
+/// Lowercased words of `text` with their bytes in `text`.
+fn tokenize(text: &str) -> Vec<(String, Range<usize>)> {
+    let mut tokens = vec![];
+    let mut start = None;
+    for (i, c) in text.char_indices().chain([(text.len(), ' ')]) {
+        match (c.is_alphanumeric(), start) {
+            (true, None) => start = Some(i),
+            (false, Some(from)) => {
+                tokens.push((text[from..i].to_lowercase(), from..i));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    tokens
+}
+
+/// The first line of `content` matching `clauses` with the bytes that matched, cut down to
+/// about [`SNIPPET_WIDTH`] around the first match.
+fn snippet(content: &str, clauses: &[Clause]) -> (String, Vec<Range<usize>>) {
+    let words: HashSet<&str> = clauses
+        .iter()
+        .flat_map(|clause| match clause {
+            Clause::Word(word) => vec![word.as_str()],
+            Clause::Phrase(words) => words.iter().map(String::as_str).collect(),
+            Clause::Prefix(_) => vec![],
+        })
+        .collect();
+    let prefixes: Vec<&str> = clauses
+        .iter()
+        .filter_map(|clause| match clause {
+            Clause::Prefix(prefix) => Some(prefix.as_str()),
+            _ => None,
+        })
+        .collect();
+    let matches =
+        |term: &str| words.contains(term) || prefixes.iter().any(|prefix| term.starts_with(prefix));
+
+    let mut lines = content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty());
+    for line in lines.clone() {
+        let highlights: Vec<Range<usize>> = tokenize(line)
+            .into_iter()
+            .filter(|(term, _)| matches(term))
+            .map(|(_, range)| range)
+            .collect();
+        if let Some(first) = highlights.first() {
+            return window(line, first.start, highlights);
+        }
+    }
+    let first_line = lines.find(|line| !line.starts_with("# ")).unwrap_or("");
+    window(first_line, 0, vec![])
+}
+
+fn window(line: &str, around: usize, highlights: Vec<Range<usize>>) -> (String, Vec<Range<usize>>) {
+    if line.len() <= SNIPPET_WIDTH {
+        return (line.to_string(), highlights);
+    }
+
+    let boundary = |mut i: usize| {
+        while !line.is_char_boundary(i) {
+            i -= 1;
+        }
+        i
+    };
+    let start = boundary(
+        around
+            .saturating_sub(SNIPPET_WIDTH / 4)
+            .min(line.len() - SNIPPET_WIDTH),
+    );
+    let end = boundary(start + SNIPPET_WIDTH);
+    let prefix = if start > 0 { "…" } else { "" };
+    let suffix = if end < line.len() { "…" } else { "" };
+    let shift = prefix.len();
+    let highlights = highlights
+        .into_iter()
+        .filter(|range| range.start >= start && range.end <= end)
+        .map(|range| range.start - start + shift..range.end - start + shift)
+        .collect();
+    (
+        format!("{}{}{}", prefix, &line[start..end], suffix),
+        highlights,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::prelude::*;
+    use std::{fs, iter, path};
+
+    fn vault() -> Vault {
+        let notes = [
+            (
+                "a.md",
+                "---\ntags: [rust]\n---\n# Ownership\nBorrowing rules of the borrow checker.",
+            ),
+            (
+                "b.md",
+                "# Cooking\nA recipe for bread. Rust is also what old pans get.",
+            ),
+            (
+                "c.md",
+                "# Checker notes\nThe borrow checker and lifetimes, lifetimes, lifetimes.",
+            ),
+        ]
+        .iter()
+        .map(|(filename, raw)| Note::parse(filename, raw).unwrap())
+        .collect();
+        Vault {
+            root: path::PathBuf::new(),
+            notes,
+            invalid: vec![],
+        }
+    }
+
+    fn ids(hits: &[SearchHit]) -> Vec<String> {
+        hits.iter().map(|hit| hit.note.id.clone()).collect()
+    }
+
+    #[test]
+    fn test_parse_query_should_read_phrases_prefixes_and_exclusions() {
+        let query = parse_query("Borrow* \"the Checker\" -pans -\"old pans\" rust").unwrap();
+
+        assert_eq!(
+            query,
+            Query {
+                required: vec![
+                    Clause::Prefix(String::from("borrow")),
+                    Clause::Phrase(vec![String::from("the"), String::from("checker")]),
+                    Clause::Word(String::from("rust")),
+                ],
+                excluded: vec![
+                    Clause::Word(String::from("pans")),
+                    Clause::Phrase(vec![String::from("old"), String::from("pans")]),
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_query_should_reject_unclosed_phrases_and_only_exclusions() {
+        assert!(matches!(parse_query("\"open"), Err(Error::InvalidQuery(_))));
+        assert!(matches!(parse_query("-nope"), Err(Error::InvalidQuery(_))));
+    }
+
+    #[test]
+    fn test_search_should_boost_tags_and_titles() {
+        let vault = vault();
+
+        let hits = search(&vault, "rust", 20).unwrap();
+
+        assert_eq!(ids(&hits), vec!["a", "b"]);
+        assert!(hits[0].score > hits[1].score);
+    }
+
+    #[test]
+    fn test_search_should_require_all_terms_and_match_phrases() {
+        let vault = vault();
+
+        assert_eq!(
+            ids(&search(&vault, "borrow checker", 20).unwrap()),
+            vec!["c", "a"]
+        );
+        assert_eq!(
+            ids(&search(&vault, "\"borrow checker\" -lifetimes", 20).unwrap()),
+            vec!["a"]
+        );
+        assert!(search(&vault, "\"checker borrow\"", 20).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_search_should_match_prefixes() {
+        let vault = vault();
+
+        assert_eq!(ids(&search(&vault, "lifetime*", 20).unwrap()), vec!["c"]);
+        assert_eq!(ids(&search(&vault, "borrow*", 20).unwrap()), vec!["a", "c"]);
+        assert_eq!(ids(&search(&vault, "borrow*", 1).unwrap()), vec!["a"]);
+    }
+
+    #[test]
+    fn test_search_should_highlight_snippets() {
+        let vault = vault();
+
+        let hits = search(&vault, "bread", 20).unwrap();
+
+        assert_eq!(
+            hits[0].snippet,
+            "A recipe for bread. Rust is also what old pans get."
+        );
+        assert_eq!(hits[0].highlights, vec![13..18]);
+    }
+
+    #[test]
+    fn test_search_should_cache_term_counts_and_read_bodies_of_hits() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        temp_dir
+            .child("a.md")
+            .write_str("# Bread\nFlour and water.")
+            .unwrap();
+        temp_dir.child("b.md").write_str("# Soup\nWater.").unwrap();
+        let vault = Vault::load_without_bodies(temp_dir.path()).unwrap();
+        search(&vault, "water", 20).unwrap();
+
+        temp_dir.child("b.md").write_str("# Soup\nStock.").unwrap();
+        let vault = Vault::load_without_bodies(temp_dir.path()).unwrap();
+        let hits = search(&vault, "\"and water\"", 20).unwrap();
+
+        let cache = fs::read_to_string(temp_dir.path().join(".ztr/search")).unwrap();
+        assert!(cache.contains("flour") && cache.contains("stock"));
+        assert_eq!(ids(&hits), vec!["a"]);
+        assert_eq!(hits[0].snippet, "Flour and water.");
+        assert!(search(&vault, "soup water", 20).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_window_should_cut_long_lines_around_match() {
+        let line = format!("{} needle {}", "a ".repeat(200), "b ".repeat(200));
+        let at = line.find("needle").unwrap();
+
+        let (snippet, highlights) = window(&line, at, iter::once(at..at + 6).collect());
+
+        assert!(snippet.starts_with('…') && snippet.ends_with('…'));
+        assert_eq!(&snippet[highlights[0].clone()], "needle");
+    }
+}
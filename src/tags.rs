@@ -0,0 +1,373 @@
+use crate::note::{frontmatter_tags, inline_tags, split_frontmatter};
+use crate::rename::FileChange;
+use crate::ztr::{Error, Note, Result, Vault};
+use serde_derive::Serialize;
+use serde_yaml::Value as Yaml;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::{fs, iter};
+
+/// How many notes carry a tag.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct TagCount {
+    pub tag: String,
+    /// Notes tagged with exactly this tag
+    pub notes: usize,
+    /// Notes tagged with it or one of its descendants, such as `project/ztr` for `project`
+    pub total: usize,
+}
+
+/// Whether `tag` is `query` or a descendant of it, ignoring case.
+pub fn tag_matches(tag: &str, query: &str) -> bool {
+    matched_len(tag, query).is_some()
+}
+
+/// Length in bytes of the part of `tag` that matches `query` when [`tag_matches`] holds.
+/// Lowercasing can change the length of a string, so the part is measured in `tag` itself.
+fn matched_len(tag: &str, query: &str) -> Option<usize> {
+    let query = query.to_lowercase();
+    tag.match_indices('/')
+        .map(|(i, _)| i)
+        .chain(iter::once(tag.len()))
+        .find(|end| tag[..*end].to_lowercase() == query)
+}
+
+/// Every tag in `vault` by name, along with the ancestors of hierarchical tags. Tags that
+/// differ only in case are counted as one, under the spelling seen first, just as
+/// [`tag_matches`] treats them as one.
+pub fn tag_counts(vault: &Vault) -> Vec<TagCount> {
+    let mut counts: BTreeMap<String, TagCount> = BTreeMap::new();
+    for note in &vault.notes {
+        let mut tags: Vec<&str> = note.tags.iter().map(String::as_str).collect();
+        tags.sort_by_key(|tag| tag.to_lowercase());
+        tags.dedup_by_key(|tag| tag.to_lowercase());
+        let mut ancestors: Vec<&str> = vec![];
+        for tag in &tags {
+            count(&mut counts, tag).notes += 1;
+            for (i, _) in tag.match_indices('/') {
+                ancestors.push(&tag[..i]);
+            }
+            ancestors.push(tag);
+        }
+        ancestors.sort_by_key(|tag| tag.to_lowercase());
+        ancestors.dedup_by_key(|tag| tag.to_lowercase());
+        for tag in ancestors {
+            count(&mut counts, tag).total += 1;
+        }
+    }
+    counts.into_values().collect()
+}
+
+/// The count of `tag` in `counts`, keyed by the tag in lowercase.
+fn count<'a>(counts: &'a mut BTreeMap<String, TagCount>, tag: &str) -> &'a mut TagCount {
+    counts
+        .entry(tag.to_lowercase())
+        .or_insert_with(|| empty_count(tag))
+}
+
+fn empty_count(tag: &str) -> TagCount {
+    TagCount {
+        tag: tag.to_string(),
+        notes: 0,
+        total: 0,
+    }
+}
+
+/// Changes renaming each of `from` to `into` in the frontmatter and the inline `#[[tag]]`s
+/// of every note, descendants included: `project/ztr` becomes `work/ztr` when `project` is
+/// renamed to `work`. Renaming several tags merges them.
+pub fn plan_retag(vault: &Vault, from: &[String], into: &str) -> Result<Vec<FileChange>> {
+    let into = into.trim();
+    if into.is_empty() || into.contains(['\n', ']', ',', ' ']) {
+        return Err(Error::InvalidTag(format!(
+            "'{}' is empty or has a space, comma, `]` or newline",
+            into
+        )));
+    }
+    let rename = |tag: &str| -> Option<String> {
+        from.iter()
+            .find_map(|from| matched_len(tag, from))
+            .map(|len| format!("{}{}", into, &tag[len..]))
+    };
+
+    let mut changes = vec![];
+    for note in &vault.notes {
+        if !note.tags.iter().any(|tag| rename(tag).is_some()) {
+            continue;
+        }
+        let Some(note_path) = &note.path else {
+            continue;
+        };
+        let before = fs::read_to_string(note_path)?;
+        let after = retag(note, &before, &rename)?;
+        if after != before {
+            changes.push(FileChange {
+                path: note_path.clone(),
+                before,
+                after,
+            });
+        }
+    }
+    Ok(changes)
+}
+
+/// `raw`, the file of `note`, with its tags renamed. The frontmatter is only written anew
+/// when one of its `tags` is renamed.
+fn retag(note: &Note, raw: &str, rename: &dyn Fn(&str) -> Option<String>) -> Result<String> {
+    let (frontmatter, content) = split_frontmatter(raw)?;
+    // Merged tags would repeat inline, so only the first of each new name is kept.
+    let new_names: Vec<String> = inline_tags(content)
+        .iter()
+        .filter_map(|tag| rename(tag))
+        .collect();
+    let seen = RefCell::new(BTreeSet::new());
+    let renamed_content = remove_inline_tags(&rename_inline_tags(content, rename), &|tag| {
+        !seen.borrow_mut().insert(tag.to_string()) && new_names.iter().any(|new| new == tag)
+    });
+    let frontmatter_tags = frontmatter_tags(&frontmatter);
+    if !frontmatter_tags.iter().any(|tag| rename(tag).is_some()) {
+        return Ok(format!(
+            "{}{}",
+            &raw[..raw.len() - content.len()],
+            renamed_content
+        ));
+    }
+
+    let mut retagged = Note::parse(&note.filename, raw)?;
+    retagged.content = renamed_content;
+    retagged.tags = vec![];
+    let tags = frontmatter_tags
+        .iter()
+        .map(|tag| rename(tag).unwrap_or(tag.clone()))
+        .chain(inline_tags(&retagged.content));
+    for tag in tags {
+        if !retagged.tags.contains(&tag) {
+            retagged.tags.push(tag);
+        }
+    }
+    let mut renamed: Vec<Yaml> = vec![];
+    for tag in &frontmatter_tags {
+        let tag = Yaml::String(rename(tag).unwrap_or(tag.clone()));
+        if !renamed.contains(&tag) {
+            renamed.push(tag);
+        }
+    }
+    retagged
+        .frontmatter
+        .insert("tags".into(), Yaml::Sequence(renamed));
+    retagged.to_markdown()
+}
+
+/// `content` with every inline `#[[tag]]` that `rename` maps replaced.
+fn rename_inline_tags(content: &str, rename: &dyn Fn(&str) -> Option<String>) -> String {
+    let mut renamed = String::with_capacity(content.len());
+    let mut rest = content;
+    while let Some(start) = rest.find("#[[") {
+        renamed.push_str(&rest[..start + 3]);
+        rest = &rest[start + 3..];
+        let Some(end) = rest.find("]]") else {
+            break;
+        };
+        let tag = &rest[..end];
+        match rename(tag.trim()).filter(|_| !tag.contains('\n')) {
+            Some(tag) => renamed.push_str(&tag),
+            None => renamed.push_str(tag),
+        }
+        rest = &rest[end..];
+    }
+    renamed.push_str(rest);
+    renamed
+}
+
+/// `content` without the inline `#[[tag]]`s `remove` picks, along with the space after each,
+/// or the one before it at the end of a line. `remove` sees the tags in order.
+pub fn remove_inline_tags(content: &str, remove: &dyn Fn(&str) -> bool) -> String {
+    let mut kept = String::with_capacity(content.len());
+    let mut rest = content;
+    while let Some(start) = rest.find("#[[") {
+        let Some(end) = rest[start..].find("]]").map(|end| start + end + 2) else {
+            break;
+        };
+        let tag = &rest[start + 3..end - 2];
+        if tag.contains('\n') || !remove(tag.trim()) {
+            kept.push_str(&rest[..start + 3]);
+            rest = &rest[start + 3..];
+            continue;
+        }
+        kept.push_str(&rest[..start]);
+        rest = match rest[end..].strip_prefix(' ') {
+            Some(after) => after,
+            None => {
+                if kept.ends_with(' ') {
+                    kept.pop();
+                }
+                &rest[end..]
+            }
+        };
+    }
+    kept.push_str(rest);
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::prelude::*;
+
+    fn vault() -> (assert_fs::TempDir, Vault) {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        temp_dir
+            .child("a.md")
+            .write_str(
+                "---\ntitle: Alpha\ntags: [project/ztr, idea]\nstatus: open\n---\nbody #[[draft]]",
+            )
+            .unwrap();
+        temp_dir
+            .child("b.md")
+            .write_str("---\nstatus: done # keep me\n---\n# Beta\n#[[project]] #[[draft]]")
+            .unwrap();
+        temp_dir
+            .child("c.md")
+            .write_str("# Gamma\n#[[idea]] #[[draft]]\nMore #[[idea]]")
+            .unwrap();
+
+        let vault = Vault::load(temp_dir.path()).unwrap();
+        (temp_dir, vault)
+    }
+
+    #[test]
+    fn test_tag_matches_should_include_descendants() {
+        assert!(tag_matches("project", "project"));
+        assert!(tag_matches("Project/ZTR", "project"));
+        assert!(!tag_matches("projects", "project"));
+        assert!(!tag_matches("project", "project/ztr"));
+    }
+
+    #[test]
+    fn test_plan_retag_should_rename_tags_whose_case_changes_length() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        temp_dir
+            .child("a.md")
+            .write_str("# Alpha\n#[[\u{212A}a/b]]")
+            .unwrap();
+        let vault = Vault::load(temp_dir.path()).unwrap();
+
+        let changes = plan_retag(&vault, &[String::from("ka")], "z").unwrap();
+
+        assert!(tag_matches("\u{212A}a/b", "ka"));
+        assert_eq!(changes[0].after, "# Alpha\n#[[z/b]]");
+    }
+
+    #[test]
+    fn test_tag_counts_should_count_ancestors() {
+        let (_temp_dir, vault) = vault();
+
+        let counts: Vec<(String, usize, usize)> = tag_counts(&vault)
+            .into_iter()
+            .map(|count| (count.tag, count.notes, count.total))
+            .collect();
+
+        assert_eq!(
+            counts,
+            vec![
+                (String::from("draft"), 3, 3),
+                (String::from("idea"), 2, 2),
+                (String::from("project"), 1, 2),
+                (String::from("project/ztr"), 1, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_plan_retag_should_rename_frontmatter_inline_and_descendant_tags() {
+        let (temp_dir, vault) = vault();
+
+        let changes = plan_retag(&vault, &[String::from("project")], "work").unwrap();
+
+        let after: Vec<&str> = changes.iter().map(|change| change.after.as_str()).collect();
+        assert_eq!(
+            after,
+            vec![
+                "---\ntitle: Alpha\ntags:\n- work/ztr\n- idea\nstatus: open\n---\nbody #[[draft]]",
+                "---\nstatus: done # keep me\n---\n# Beta\n#[[work]] #[[draft]]",
+            ]
+        );
+        assert_eq!(changes[0].path, temp_dir.path().join("a.md"));
+    }
+
+    #[test]
+    fn test_tag_counts_should_ignore_case() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        temp_dir
+            .child("a.md")
+            .write_str("# A\n#[[Project/x]] #[[project]]")
+            .unwrap();
+        temp_dir
+            .child("b.md")
+            .write_str("# B\n#[[PROJECT]]")
+            .unwrap();
+        let vault = Vault::load(temp_dir.path()).unwrap();
+
+        let counts: Vec<(String, usize, usize)> = tag_counts(&vault)
+            .into_iter()
+            .map(|count| (count.tag, count.notes, count.total))
+            .collect();
+
+        assert_eq!(
+            counts,
+            vec![
+                (String::from("project"), 2, 2),
+                (String::from("Project/x"), 1, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_plan_retag_should_merge_tags() {
+        let (temp_dir, vault) = vault();
+
+        let changes = plan_retag(
+            &vault,
+            &[String::from("draft"), String::from("idea")],
+            "seed",
+        )
+        .unwrap();
+        crate::rename::write_changes(&changes).unwrap();
+
+        temp_dir.child("c.md").assert("# Gamma\n#[[seed]]\nMore");
+        temp_dir
+            .child("b.md")
+            .assert("---\nstatus: done # keep me\n---\n# Beta\n#[[project]] #[[seed]]");
+        let vault = Vault::load(&vault.root).unwrap();
+        let counts: Vec<(String, usize)> = tag_counts(&vault)
+            .into_iter()
+            .map(|count| (count.tag, count.notes))
+            .collect();
+        assert_eq!(
+            counts,
+            vec![
+                (String::from("project"), 1),
+                (String::from("project/ztr"), 1),
+                (String::from("seed"), 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_remove_inline_tags_should_drop_tag_and_following_space() {
+        let content = "# A\n\n#[[fleeting]] #[[idea]] #[[fleeting/x]]";
+
+        let kept = remove_inline_tags(content, &|tag| tag == "fleeting");
+
+        assert_eq!(kept, "# A\n\n#[[idea]] #[[fleeting/x]]");
+    }
+
+    #[test]
+    fn test_plan_retag_should_reject_invalid_tags() {
+        let (_temp_dir, vault) = vault();
+
+        let result = plan_retag(&vault, &[String::from("idea")], "two words");
+
+        assert!(matches!(result, Err(Error::InvalidTag(_))));
+    }
+}
@@ -0,0 +1,340 @@
+use crate::helpers;
+use crate::ztr::{Error, Result};
+use handlebars::template::{HelperTemplate, Parameter, Template, TemplateElement};
+use handlebars::{Handlebars, Path};
+use serde_derive::Deserialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::{fs, io, path};
+
+/// Named templates of a vault, keyed by file stem.
+pub type Templates = BTreeMap<String, TemplateSource>;
+
+/// A template body and the variable defaults declared in its front-matter.
+///
+/// The front-matter is a TOML block fenced by `+++` lines at the very top,
+/// so it cannot be confused with YAML front-matter the template renders:
+///
+/// ```text
+/// +++
+/// [vars]
+/// author = "Unknown"
+/// +++
+/// # {{title}} by {{author}}
+/// ```
+#[derive(Default, Debug, PartialEq)]
+pub struct TemplateSource {
+    pub body: String,
+    pub defaults: BTreeMap<String, Value>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct FrontMatter {
+    vars: BTreeMap<String, Value>,
+}
+
+const FRONT_MATTER_FENCE: &str = "+++";
+
+impl TemplateSource {
+    pub fn parse(name: &str, raw: &str) -> Result<Self> {
+        let Some(rest) = raw
+            .strip_prefix(FRONT_MATTER_FENCE)
+            .and_then(|rest| rest.strip_prefix('\n').or(rest.strip_prefix("\r\n")))
+        else {
+            return Ok(Self {
+                body: raw.to_string(),
+                defaults: BTreeMap::new(),
+            });
+        };
+
+        let mut front_matter_len = 0;
+        for line in rest.split_inclusive('\n') {
+            if line.trim_end() == FRONT_MATTER_FENCE {
+                let front_matter: FrontMatter =
+                    toml::from_str(&rest[..front_matter_len]).map_err(|e| {
+                        Error::Config(format!("front-matter of template '{}': {}", name, e))
+                    })?;
+                return Ok(Self {
+                    body: rest[front_matter_len + line.len()..].to_string(),
+                    defaults: front_matter.vars,
+                });
+            }
+            front_matter_len += line.len();
+        }
+
+        Err(Error::Config(format!(
+            "front-matter of template '{}' is not closed with '{}'",
+            name, FRONT_MATTER_FENCE
+        )))
+    }
+}
+
+pub fn templates_dir(zk_root: &path::Path) -> path::PathBuf {
+    zk_root.join(".ztr").join("templates")
+}
+
+/// Reads every `*.hbs` file in `.ztr/templates`. A vault without the directory has no templates.
+pub fn load(zk_root: &path::Path) -> Result<Templates> {
+    let entries = match fs::read_dir(templates_dir(zk_root)) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Templates::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut templates = Templates::new();
+    for entry in entries {
+        let template_path = entry?.path();
+        if template_path.extension().is_some_and(|ext| ext == "hbs") {
+            if let Some(name) = template_path.file_stem() {
+                let name = name.to_string_lossy().to_string();
+                let raw = fs::read_to_string(&template_path)?;
+                let source = TemplateSource::parse(&name, &raw)?;
+                templates.insert(name, source);
+            }
+        }
+    }
+    Ok(templates)
+}
+
+/// Registers every template by name, which also makes each one usable as a partial.
+pub fn register(hb: &mut Handlebars, templates: &Templates) -> Result<()> {
+    for (name, source) in templates {
+        hb.register_template_string(name, &source.body)?;
+    }
+    Ok(())
+}
+
+/// Variables the registered template `name` reads from the root of its data,
+/// following partials. Names only used as `#if`/`#unless` conditions are optional
+/// and left out.
+pub fn referenced_vars(hb: &Handlebars, name: &str) -> ReferencedVars {
+    let mut collector = VarCollector {
+        hb,
+        required: BTreeSet::new(),
+        optional: BTreeSet::new(),
+        partials: BTreeSet::from([name.to_string()]),
+    };
+    if let Some(template) = hb.get_template(name) {
+        collector.template(template, false);
+    }
+
+    ReferencedVars {
+        vars: &collector.required - &collector.optional,
+        partials: collector.partials,
+    }
+}
+
+pub struct ReferencedVars {
+    pub vars: BTreeSet<String>,
+    /// Every template that was walked, starting with the one asked for
+    pub partials: BTreeSet<String>,
+}
+
+struct VarCollector<'a> {
+    hb: &'a Handlebars<'a>,
+    required: BTreeSet<String>,
+    optional: BTreeSet<String>,
+    partials: BTreeSet<String>,
+}
+
+impl VarCollector<'_> {
+    /// `scoped` is set inside blocks such as `#each` that change the context,
+    /// where only `@root.` paths reach the note.
+    fn template(&mut self, template: &Template, scoped: bool) {
+        for element in &template.elements {
+            self.element(element, scoped);
+        }
+    }
+
+    fn element(&mut self, element: &TemplateElement, scoped: bool) {
+        match element {
+            TemplateElement::Expression(ht) | TemplateElement::HtmlExpression(ht) => {
+                if ht.params.is_empty() && ht.hash.is_empty() {
+                    if let Some(var) = helper_or_var(&ht.name) {
+                        self.var(var, scoped);
+                    }
+                } else {
+                    self.arguments(ht, scoped);
+                }
+            }
+            TemplateElement::HelperBlock(ht) => self.block(ht, scoped),
+            TemplateElement::PartialExpression(dt) | TemplateElement::PartialBlock(dt) => {
+                let Some(name) = param_name(&dt.name) else {
+                    return;
+                };
+                if let Some(inner) = &dt.template {
+                    self.template(inner, scoped);
+                }
+                if scoped || !dt.params.is_empty() || !self.partials.insert(name.to_string()) {
+                    return;
+                }
+                if let Some(partial) = self.hb.get_template(name) {
+                    self.template(partial, false);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn block(&mut self, ht: &HelperTemplate, scoped: bool) {
+        let helper = param_name(&ht.name).unwrap_or_default();
+        self.arguments(ht, scoped);
+
+        if matches!(helper, "if" | "unless") {
+            for param in &ht.params {
+                if let Some(var) = param_name(param) {
+                    self.optional.insert(root_var(var).to_string());
+                }
+            }
+        }
+
+        let changes_context = !matches!(helper, "if" | "unless");
+        if let Some(inner) = &ht.template {
+            self.template(inner, scoped || changes_context);
+        }
+        if let Some(inverse) = &ht.inverse {
+            self.template(inverse, scoped);
+        }
+    }
+
+    fn arguments(&mut self, ht: &HelperTemplate, scoped: bool) {
+        for param in ht.params.iter().chain(ht.hash.values()) {
+            self.param(param, scoped);
+        }
+    }
+
+    fn param(&mut self, param: &Parameter, scoped: bool) {
+        match param {
+            Parameter::Path(_) | Parameter::Name(_) => {
+                if let Some(var) = param_name(param) {
+                    self.var(var, scoped);
+                }
+            }
+            Parameter::Subexpression(subexpression) => self.element(&subexpression.element, scoped),
+            Parameter::Literal(_) => {}
+        }
+    }
+
+    fn var(&mut self, raw: &str, scoped: bool) {
+        let raw = match raw.strip_prefix("@root.") {
+            Some(rooted) => rooted,
+            None if scoped => return,
+            None => raw.strip_prefix("this.").unwrap_or(raw),
+        };
+        if raw.starts_with('@') || raw.starts_with('.') || raw == "this" {
+            return;
+        }
+        self.required.insert(root_var(raw).to_string());
+    }
+}
+
+fn param_name(param: &Parameter) -> Option<&str> {
+    match param {
+        Parameter::Name(name) => Some(name),
+        Parameter::Path(Path::Relative((_, raw))) | Parameter::Path(Path::Local((_, _, raw))) => {
+            Some(raw)
+        }
+        _ => None,
+    }
+}
+
+/// The variable an expression without arguments reads, unless it calls a helper.
+fn helper_or_var(name: &Parameter) -> Option<&str> {
+    param_name(name).filter(|name| !helpers::is_helper(name))
+}
+
+fn root_var(raw: &str) -> &str {
+    let var = raw.split(['.', '/']).next().unwrap_or(raw);
+    var.trim_start_matches('[').trim_end_matches(']')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::prelude::*;
+
+    fn vars_of(template: &str) -> Vec<String> {
+        let mut hb = Handlebars::new();
+        helpers::register(&mut hb);
+        hb.register_template_string("partial", "{{source}} {{#each tags}}{{this}}{{/each}}")
+            .unwrap();
+        hb.register_template_string("note", template).unwrap();
+
+        referenced_vars(&hb, "note").vars.into_iter().collect()
+    }
+
+    #[test]
+    fn test_load_should_key_templates_by_name() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        temp_dir
+            .child(".ztr/templates/literature.hbs")
+            .write_str("# {{title}}")
+            .unwrap();
+        temp_dir
+            .child(".ztr/templates/README.md")
+            .write_str("not a template")
+            .unwrap();
+
+        let templates = load(temp_dir.path()).unwrap();
+
+        assert_eq!(templates.len(), 1);
+        assert_eq!(templates["literature"].body, "# {{title}}");
+    }
+
+    #[test]
+    fn test_load_should_allow_missing_directory() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+
+        let templates = load(temp_dir.path()).unwrap();
+
+        assert!(templates.is_empty());
+    }
+
+    #[test]
+    fn test_parse_should_split_front_matter() {
+        let source =
+            TemplateSource::parse("t", "+++\n[vars]\nauthor = \"Unknown\"\n+++\n# {{author}}")
+                .unwrap();
+
+        assert_eq!(source.body, "# {{author}}");
+        assert_eq!(source.defaults["author"], Value::from("Unknown"));
+    }
+
+    #[test]
+    fn test_parse_should_keep_yaml_front_matter_in_body() {
+        let source = TemplateSource::parse("t", "---\ntitle: {{title}}\n---\n").unwrap();
+
+        assert_eq!(source.body, "---\ntitle: {{title}}\n---\n");
+        assert!(source.defaults.is_empty());
+    }
+
+    #[test]
+    fn test_parse_should_fail_on_unclosed_front_matter() {
+        assert!(TemplateSource::parse("t", "+++\n[vars]\n").is_err());
+    }
+
+    #[test]
+    fn test_referenced_vars_should_skip_helpers_and_scoped_names() {
+        let vars = vars_of(
+            "{{author}} {{upper year.value}} {{now tz=zone}} {{uuid}} \
+             {{#each tags as |tag|}}{{tag}}{{name}}{{@root.url}}{{/each}}",
+        );
+
+        assert_eq!(vars, vec!["author", "tags", "url", "year", "zone"]);
+    }
+
+    #[test]
+    fn test_referenced_vars_should_treat_conditions_as_optional() {
+        let vars = vars_of("{{#if source}}{{source}}{{else}}{{fallback}}{{/if}}");
+
+        assert_eq!(vars, vec!["fallback"]);
+    }
+
+    #[test]
+    fn test_referenced_vars_should_follow_partials() {
+        let vars = vars_of("{{> partial}} {{lower (upper author)}}");
+
+        assert_eq!(vars, vec!["author", "source", "tags"]);
+    }
+}
@@ -0,0 +1,223 @@
+use crate::atomic;
+use crate::ztr::{Error, Note, Result, Vault};
+use chrono::{DateTime, Local};
+use serde_derive::{Deserialize, Serialize};
+use std::{fs, io, path};
+
+const MANIFEST: &str = "manifest.json";
+
+/// A note in the trash, as recorded in the manifest.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TrashEntry {
+    pub id: String,
+    /// Where the note was, relative to the vault root
+    pub path: path::PathBuf,
+    /// Name of the note's file in the trash directory
+    pub file: String,
+    pub trashed: DateTime<Local>,
+}
+
+pub fn trash_dir(zk_root: &path::Path) -> path::PathBuf {
+    zk_root.join(".ztr").join("trash")
+}
+
+/// Moves `note` into the trash. Notes other notes link to are kept unless `force` is set.
+pub fn remove(vault: &Vault, note: &Note, force: bool) -> Result<TrashEntry> {
+    let graph = vault.graph();
+    let mut sources: Vec<String> = graph
+        .backlinks(&note.id)
+        .filter(|edge| edge.source != note.id)
+        .map(|edge| edge.source.clone())
+        .collect();
+    sources.dedup();
+    if !sources.is_empty() && !force {
+        return Err(Error::HasBacklinks {
+            id: note.id.clone(),
+            sources,
+        });
+    }
+
+    let note_path = note
+        .path
+        .as_deref()
+        .ok_or_else(|| Error::NotFound(format!("note '{}' has no file", note.id)))?;
+    let trashed = Local::now();
+    let entry = TrashEntry {
+        id: note.id.clone(),
+        path: note_path
+            .strip_prefix(&vault.root)
+            .unwrap_or(note_path)
+            .to_path_buf(),
+        file: format!("{}-{}", trashed.format("%Y%m%dT%H%M%S%.3f"), note.filename),
+        trashed,
+    };
+
+    let dir = trash_dir(&vault.root);
+    fs::create_dir_all(&dir)?;
+    let mut entries = list(&vault.root)?;
+    let trashed_path = dir.join(&entry.file);
+    fs::rename(note_path, &trashed_path)?;
+    entries.push(entry.clone());
+    if let Err(e) = write_manifest(&vault.root, &entries) {
+        let _ = fs::rename(&trashed_path, note_path);
+        return Err(e);
+    }
+    Ok(entry)
+}
+
+/// Notes in the trash, oldest first.
+pub fn list(zk_root: &path::Path) -> Result<Vec<TrashEntry>> {
+    let manifest_path = trash_dir(zk_root).join(MANIFEST);
+    let raw = match fs::read_to_string(&manifest_path) {
+        Ok(raw) => raw,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e.into()),
+    };
+    serde_json::from_str(&raw)
+        .map_err(|e| Error::Config(format!("{}: {}", manifest_path.display(), e)))
+}
+
+/// Moves the most recently trashed note with the ID or trash file `query` back to where it
+/// was, unless another note has taken its place since.
+pub fn restore(zk_root: &path::Path, query: &str) -> Result<path::PathBuf> {
+    let mut entries = list(zk_root)?;
+    let index = entries
+        .iter()
+        .rposition(|entry| entry.id == query || entry.file == query)
+        .ok_or_else(|| Error::NotFound(format!("no note '{}' in the trash", query)))?;
+
+    let restored = zk_root.join(&entries[index].path);
+    if restored.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists", restored.display()),
+        )
+        .into());
+    }
+    if let Some(dir) = restored.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let trashed_path = trash_dir(zk_root).join(&entries[index].file);
+    fs::rename(&trashed_path, &restored)?;
+    entries.remove(index);
+    if let Err(e) = write_manifest(zk_root, &entries) {
+        let _ = fs::rename(&restored, &trashed_path);
+        return Err(e);
+    }
+    Ok(restored)
+}
+
+/// Deletes every note in the trash for good and returns how many there were.
+pub fn empty(zk_root: &path::Path) -> Result<usize> {
+    let entries = list(zk_root)?;
+    let dir = trash_dir(zk_root);
+    for entry in &entries {
+        match fs::remove_file(dir.join(&entry.file)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+    }
+    write_manifest(zk_root, &[])?;
+    Ok(entries.len())
+}
+
+fn write_manifest(zk_root: &path::Path, entries: &[TrashEntry]) -> Result<()> {
+    let json = serde_json::to_string_pretty(entries).map_err(io::Error::from)?;
+    atomic::write(&trash_dir(zk_root).join(MANIFEST), json + "\n")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::prelude::*;
+
+    fn vault() -> (assert_fs::TempDir, Vault) {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        temp_dir.child("a.md").write_str("# Alpha\n[[a]]").unwrap();
+        temp_dir
+            .child("sub/b.md")
+            .write_str("# Beta\n[[a]]")
+            .unwrap();
+
+        let vault = Vault::load(temp_dir.path()).unwrap();
+        (temp_dir, vault)
+    }
+
+    #[test]
+    fn test_remove_should_refuse_notes_with_backlinks() {
+        let (temp_dir, vault) = vault();
+
+        let result = remove(&vault, vault.resolve("a").unwrap(), false);
+
+        assert!(matches!(result, Err(Error::HasBacklinks { sources, .. }) if sources == ["b"]));
+        assert!(temp_dir.child("a.md").exists());
+    }
+
+    #[test]
+    fn test_remove_should_move_note_to_trash() {
+        let (temp_dir, vault) = vault();
+
+        let entry = remove(&vault, vault.resolve("b").unwrap(), false).unwrap();
+
+        assert!(!temp_dir.child("sub/b.md").exists());
+        assert_eq!(entry.path, path::Path::new("sub/b.md"));
+        temp_dir
+            .child(".ztr/trash")
+            .child(&entry.file)
+            .assert("# Beta\n[[a]]");
+        assert_eq!(list(temp_dir.path()).unwrap(), vec![entry]);
+    }
+
+    #[test]
+    fn test_remove_should_keep_note_when_manifest_cannot_be_written() {
+        let (temp_dir, vault) = vault();
+        temp_dir
+            .child(".ztr/trash/.manifest.json.ztr-tmp/keep")
+            .write_str("")
+            .unwrap();
+
+        let result = remove(&vault, vault.resolve("b").unwrap(), false);
+
+        assert!(result.is_err());
+        temp_dir.child("sub/b.md").assert("# Beta\n[[a]]");
+        let trashed = fs::read_dir(temp_dir.path().join(".ztr/trash")).unwrap();
+        assert_eq!(trashed.count(), 1);
+    }
+
+    #[test]
+    fn test_restore_should_move_note_back() {
+        let (temp_dir, vault) = vault();
+        remove(&vault, vault.resolve("a").unwrap(), true).unwrap();
+
+        let restored = restore(temp_dir.path(), "a").unwrap();
+
+        assert_eq!(restored, temp_dir.path().join("a.md"));
+        temp_dir.child("a.md").assert("# Alpha\n[[a]]");
+        assert!(list(temp_dir.path()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_restore_should_not_overwrite_notes() {
+        let (temp_dir, vault) = vault();
+        remove(&vault, vault.resolve("b").unwrap(), false).unwrap();
+        temp_dir.child("sub/b.md").write_str("new").unwrap();
+
+        let result = restore(temp_dir.path(), "b");
+
+        assert!(matches!(result, Err(Error::Io(e)) if e.kind() == io::ErrorKind::AlreadyExists));
+        assert_eq!(list(temp_dir.path()).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_empty_should_delete_trashed_notes() {
+        let (temp_dir, vault) = vault();
+        let entry = remove(&vault, vault.resolve("b").unwrap(), false).unwrap();
+
+        let count = empty(temp_dir.path()).unwrap();
+
+        assert_eq!(count, 1);
+        assert!(!temp_dir.child(".ztr/trash").child(entry.file).exists());
+        assert!(list(temp_dir.path()).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,358 @@
+use crate::index;
+use crate::links::{snippet, Link};
+use crate::tags::tag_matches;
+use crate::ztr::{Error, Graph, Note, Result};
+use chrono::{DateTime, Local};
+use std::collections::BTreeSet;
+use std::{fs, path};
+
+/// Every note of a zettelkasten, read from the Markdown files below its root.
+pub struct Vault {
+    pub root: path::PathBuf,
+    pub notes: Vec<Note>,
+    /// Notes that could not be parsed, with the reason
+    pub invalid: Vec<(path::PathBuf, Error)>,
+}
+
+/// Which notes [`Vault::list`] keeps. Every condition that is set must hold.
+#[derive(Default)]
+pub struct NoteFilter {
+    /// The note carries all of these tags or one of their descendants
+    pub tags: Vec<String>,
+    /// Created at or after
+    pub since: Option<DateTime<Local>>,
+    /// Created before
+    pub until: Option<DateTime<Local>>,
+    /// Only notes with one of these IDs, such as the results of a saved search
+    pub ids: Option<BTreeSet<String>>,
+}
+
+/// A link to a note, from the note `source`.
+pub struct Backlink<'a> {
+    pub source: &'a Note,
+    pub link: Link,
+    /// The line of the link
+    pub snippet: String,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum SortKey {
+    #[default]
+    Id,
+    Title,
+    Created,
+    Modified,
+}
+
+impl NoteFilter {
+    pub fn matches(&self, note: &Note) -> bool {
+        let has_tags = self
+            .tags
+            .iter()
+            .all(|query| note.tags.iter().any(|tag| tag_matches(tag, query)));
+        let after_since = match (self.since, note.created) {
+            (Some(since), Some(created)) => created >= since,
+            (Some(_), None) => false,
+            (None, _) => true,
+        };
+        let before_until = match (self.until, note.created) {
+            (Some(until), Some(created)) => created < until,
+            (Some(_), None) => false,
+            (None, _) => true,
+        };
+        let in_ids = self.ids.as_ref().is_none_or(|ids| ids.contains(&note.id));
+        has_tags && after_since && before_until && in_ids
+    }
+}
+
+impl Vault {
+    /// Reads every `*.md` file below `zk_root`, skipping hidden directories such as `.ztr`.
+    /// Notes unchanged since the last load come from the index in `.ztr/index`.
+    pub fn load(zk_root: &path::Path) -> Result<Vault> {
+        index::load_vault(zk_root, true)
+    }
+
+    /// Like [`Vault::load`], but leaves the `content` and `frontmatter` of every note empty,
+    /// so the files of notes unchanged since the last load are not read. Enough for titles,
+    /// tags, dates and links, but not for writing notes back.
+    pub fn load_without_bodies(zk_root: &path::Path) -> Result<Vault> {
+        index::load_vault(zk_root, false)
+    }
+
+    pub fn graph(&self) -> Graph {
+        Graph::build(&self.notes)
+    }
+
+    /// Links to `target` from every note, ordered by the linking note's ID and line.
+    pub fn backlinks(&self, target: &Note) -> Vec<Backlink<'_>> {
+        let graph = self.graph();
+        graph
+            .backlinks(&target.id)
+            .filter_map(|edge| {
+                let source = self.notes.iter().find(|note| note.id == edge.source)?;
+                let line = source
+                    .content
+                    .lines()
+                    .nth(edge.link.line - source.content_offset - 1)
+                    .unwrap_or("");
+                Some(Backlink {
+                    source,
+                    link: edge.link.clone(),
+                    snippet: snippet(line, edge.link.column),
+                })
+            })
+            .collect()
+    }
+
+    /// Notes matching `filter`, ordered by `sort` and then by ID.
+    pub fn list(&self, filter: &NoteFilter, sort: SortKey, limit: Option<usize>) -> Vec<&Note> {
+        let mut notes: Vec<&Note> = self.notes.iter().filter(|n| filter.matches(n)).collect();
+        notes.sort_by(|a, b| {
+            let by_key = match sort {
+                SortKey::Id => std::cmp::Ordering::Equal,
+                SortKey::Title => a.title.to_lowercase().cmp(&b.title.to_lowercase()),
+                SortKey::Created => a.created.cmp(&b.created),
+                SortKey::Modified => a.modified.cmp(&b.modified),
+            };
+            by_key.then_with(|| a.id.cmp(&b.id))
+        });
+        notes.truncate(limit.unwrap_or(notes.len()));
+        notes
+    }
+
+    /// Finds the note with the ID or filename `query`, or else the one whose title is `query`
+    /// or contains it, ignoring case.
+    pub fn resolve(&self, query: &str) -> Result<&Note> {
+        if let Some(note) = self
+            .notes
+            .iter()
+            .find(|note| note.id == query || note.filename == query)
+        {
+            return Ok(note);
+        }
+
+        let query_lower = query.to_lowercase();
+        let exact: Vec<&Note> = self
+            .notes
+            .iter()
+            .filter(|note| note.title.to_lowercase() == query_lower)
+            .collect();
+        let matches = if exact.is_empty() {
+            self.notes
+                .iter()
+                .filter(|note| note.title.to_lowercase().contains(&query_lower))
+                .collect()
+        } else {
+            exact
+        };
+
+        match matches.as_slice() {
+            [note] => Ok(note),
+            [] => Err(Error::NotFound(format!("no note matches '{}'", query))),
+            _ => Err(Error::Ambiguous {
+                query: query.to_string(),
+                candidates: matches
+                    .iter()
+                    .map(|note| format!("{} ({})", note.id, note.title))
+                    .collect(),
+            }),
+        }
+    }
+
+    /// Path of the note [`Vault::resolve`] finds, or of an unparsable note
+    /// with the ID or filename `query`, so broken notes can still be opened.
+    pub fn resolve_path(&self, query: &str) -> Result<path::PathBuf> {
+        let invalid = self.invalid.iter().find(|(note_path, _)| {
+            note_path.file_name().is_some_and(|name| name == query)
+                || note_path.file_stem().is_some_and(|stem| stem == query)
+        });
+        match (self.resolve(query), invalid) {
+            (Ok(note), _) => Ok(note.path.clone().unwrap_or(self.root.join(&note.filename))),
+            (Err(Error::NotFound(_)), Some((note_path, _))) => Ok(note_path.clone()),
+            (Err(e), _) => Err(e),
+        }
+    }
+}
+
+/// Paths of the Markdown files below `dir`, sorted.
+pub fn note_paths(dir: &path::Path) -> Result<Vec<path::PathBuf>> {
+    let mut paths = vec![];
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let entry_path = entry.path();
+        let hidden = entry.file_name().to_string_lossy().starts_with('.');
+        if entry.file_type()?.is_dir() {
+            if !hidden {
+                paths.extend(note_paths(&entry_path)?);
+            }
+        } else if entry_path.extension().is_some_and(|ext| ext == "md") {
+            paths.push(entry_path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::prelude::*;
+
+    fn vault() -> (assert_fs::TempDir, Vault) {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        temp_dir
+            .child("b.md")
+            .write_str("---\ncreated: 2026-02-01\ntags: [project]\n---\n# Beta")
+            .unwrap();
+        temp_dir
+            .child("a.md")
+            .write_str("---\ncreated: 2026-03-01\n---\n# Alpha\n#[[project]] #[[idea]]")
+            .unwrap();
+        temp_dir
+            .child("sub/c.md")
+            .write_str("---\ncreated: 2026-01-01\n---\n# Gamma")
+            .unwrap();
+        temp_dir
+            .child(".ztr/templates/t.md")
+            .write_str("not a note")
+            .unwrap();
+        temp_dir
+            .child("broken.md")
+            .write_str("---\ntags: [\n---\n")
+            .unwrap();
+
+        let vault = Vault::load(temp_dir.path()).unwrap();
+        (temp_dir, vault)
+    }
+
+    fn ids(notes: Vec<&Note>) -> Vec<&str> {
+        notes.iter().map(|note| note.id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_load_should_read_nested_notes_and_skip_hidden() {
+        let (_temp_dir, vault) = vault();
+
+        assert_eq!(vault.notes.len(), 3);
+        assert_eq!(vault.invalid.len(), 1);
+        assert!(vault.invalid[0].0.ends_with("broken.md"));
+    }
+
+    #[test]
+    fn test_resolve_should_find_by_id_then_title() {
+        let (_temp_dir, vault) = vault();
+
+        assert_eq!(vault.resolve("b").unwrap().title, "Beta");
+        assert_eq!(vault.resolve("c.md").unwrap().title, "Gamma");
+        assert_eq!(vault.resolve("alpha").unwrap().id, "a");
+        assert_eq!(vault.resolve("gam").unwrap().id, "c");
+    }
+
+    #[test]
+    fn test_resolve_should_report_ambiguous_and_missing() {
+        let (_temp_dir, vault) = vault();
+
+        assert!(matches!(
+            vault.resolve("A"),
+            Err(Error::Ambiguous { candidates, .. }) if candidates.len() == 3
+        ));
+        assert!(matches!(vault.resolve("zeta"), Err(Error::NotFound(_))));
+    }
+
+    #[test]
+    fn test_resolve_path_should_find_invalid_notes() {
+        let (temp_dir, vault) = vault();
+
+        assert_eq!(
+            vault.resolve_path("broken").unwrap(),
+            temp_dir.path().join("broken.md")
+        );
+    }
+
+    #[test]
+    fn test_backlinks_should_report_line_and_snippet() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        temp_dir.child("a.md").write_str("# Alpha").unwrap();
+        temp_dir
+            .child("b.md")
+            .write_str("---\ntags: []\n---\n# Beta\n\n  More in [[Alpha]].")
+            .unwrap();
+        temp_dir.child("c.md").write_str("# Gamma\n[[b]]").unwrap();
+        let vault = Vault::load(temp_dir.path()).unwrap();
+
+        let backlinks = vault.backlinks(vault.resolve("a").unwrap());
+
+        assert_eq!(backlinks.len(), 1);
+        assert_eq!(backlinks[0].source.id, "b");
+        assert_eq!((backlinks[0].link.line, backlinks[0].link.column), (6, 11));
+        assert_eq!(backlinks[0].snippet, "More in [[Alpha]].");
+    }
+
+    #[test]
+    fn test_list_should_filter_by_tags() {
+        let (_temp_dir, vault) = vault();
+        let filter = NoteFilter {
+            tags: vec![String::from("project")],
+            ..NoteFilter::default()
+        };
+
+        assert_eq!(ids(vault.list(&filter, SortKey::Id, None)), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_list_should_match_descendant_tags() {
+        let (temp_dir, _vault) = vault();
+        temp_dir
+            .child("d.md")
+            .write_str("---\ntags: [Project/ztr]\n---\n# Delta")
+            .unwrap();
+        let vault = Vault::load(temp_dir.path()).unwrap();
+        let filter = NoteFilter {
+            tags: vec![String::from("project")],
+            ..NoteFilter::default()
+        };
+
+        assert_eq!(
+            ids(vault.list(&filter, SortKey::Id, None)),
+            vec!["a", "b", "d"]
+        );
+    }
+
+    #[test]
+    fn test_list_should_filter_by_created_range() {
+        let (_temp_dir, vault) = vault();
+        let filter = NoteFilter {
+            since: crate::note::parse_date("2026-01-15"),
+            until: crate::note::parse_date("2026-03-01"),
+            ..NoteFilter::default()
+        };
+
+        assert_eq!(ids(vault.list(&filter, SortKey::Id, None)), vec!["b"]);
+    }
+
+    #[test]
+    fn test_list_should_filter_by_ids() {
+        let (_temp_dir, vault) = vault();
+        let filter = NoteFilter {
+            ids: Some(BTreeSet::from([String::from("c"), String::from("a")])),
+            ..NoteFilter::default()
+        };
+
+        assert_eq!(ids(vault.list(&filter, SortKey::Id, None)), vec!["a", "c"]);
+    }
+
+    #[test]
+    fn test_list_should_sort_and_limit() {
+        let (_temp_dir, vault) = vault();
+        let filter = NoteFilter::default();
+
+        assert_eq!(
+            ids(vault.list(&filter, SortKey::Created, None)),
+            vec!["c", "b", "a"]
+        );
+        assert_eq!(
+            ids(vault.list(&filter, SortKey::Title, Some(2))),
+            vec!["a", "b"]
+        );
+    }
+}